@@ -0,0 +1,20 @@
+CREATE TABLE playlists (
+    id INTEGER PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    name TEXT NOT NULL COLLATE NOCASE,
+    owner_id INTEGER NOT NULL,
+    UNIQUE (guild_id, name)
+);
+
+-- Tracks play in id order
+CREATE TABLE playlist_tracks (
+    id INTEGER PRIMARY KEY,
+    playlist_id INTEGER NOT NULL REFERENCES playlists (id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    title TEXT NOT NULL,
+    duration_ms INTEGER,
+    source TEXT NOT NULL,
+    added_by INTEGER NOT NULL
+);
+
+CREATE INDEX playlist_tracks_playlist ON playlist_tracks (playlist_id, id);
@@ -0,0 +1,13 @@
+-- Tracks in the order they started playing, newest last
+CREATE TABLE play_history (
+    id INTEGER PRIMARY KEY,
+    guild_id INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    title TEXT NOT NULL,
+    duration_ms INTEGER,
+    source TEXT NOT NULL,
+    requester INTEGER NOT NULL,
+    played_at INTEGER NOT NULL
+);
+
+CREATE INDEX play_history_guild ON play_history (guild_id, id);
@@ -0,0 +1,70 @@
+use serenity::all::{Context, CreateMessage, Guild, GuildId, UserId};
+use serenity::http::Http;
+
+use crate::config::GuildAccessConfig;
+
+/// Whether the bot should operate in a guild. Denials take precedence over the allowlist.
+pub fn is_allowed(config: &GuildAccessConfig, guild_id: GuildId) -> bool {
+    let id = guild_id.get();
+    !config.deny.contains(&id) && (config.allow.is_empty() || config.allow.contains(&id))
+}
+
+/// Say goodbye in the guild's system channel, when there is one, and leave.
+pub async fn leave(ctx: &Context, config: &GuildAccessConfig, guild: &Guild) {
+    tracing::warn!(
+        "Leaving guild {} ({}): not allowed by configuration",
+        guild.name,
+        guild.id
+    );
+
+    if let Some(channel_id) = guild.system_channel_id {
+        let message = CreateMessage::new().content(&config.leave_message);
+        if let Err(e) = channel_id.send_message(ctx, message).await {
+            tracing::debug!("Could not post leave message in guild {}: {}", guild.id, e);
+        }
+    }
+
+    if let Err(e) = guild.id.leave(ctx).await {
+        tracing::error!("Failed to leave guild {}: {}", guild.id, e);
+    }
+}
+
+/// Application owner, or every team member for team-owned applications.
+pub async fn owners(http: &Http) -> serenity::Result<Vec<UserId>> {
+    let info = http.get_current_application_info().await?;
+    Ok(match info.team {
+        Some(team) => team
+            .members
+            .into_iter()
+            .map(|member| member.user.id)
+            .collect(),
+        None => info.owner.into_iter().map(|owner| owner.id).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(&[], &[], 1, true)]
+    #[case(&[1, 2], &[], 1, true)]
+    #[case(&[1, 2], &[], 3, false)]
+    #[case(&[], &[3], 3, false)]
+    #[case(&[], &[3], 1, true)]
+    #[case(&[1], &[1], 1, false)]
+    fn test_is_allowed(
+        #[case] allow: &[u64],
+        #[case] deny: &[u64],
+        #[case] guild_id: u64,
+        #[case] expected: bool,
+    ) {
+        let config = GuildAccessConfig {
+            allow: allow.to_vec(),
+            deny: deny.to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(is_allowed(&config, GuildId::new(guild_id)), expected);
+    }
+}
@@ -0,0 +1,38 @@
+use serenity::all::{Channel, ChannelId, CreateMessage, GuildId};
+use serenity::http::Http;
+
+/// Post a one-off announcement to a guild.
+/// Falls back to the guild's system channel when no channel is given.
+pub async fn announce(
+    http: &Http,
+    guild_id: GuildId,
+    channel_id: Option<ChannelId>,
+    message: &str,
+    tts: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let channel_id = match channel_id {
+        Some(channel_id) => {
+            match channel_id.to_channel(http).await? {
+                Channel::Guild(channel) if channel.guild_id == guild_id => {}
+                _ => return Err(format!("Channel {channel_id} is not in guild {guild_id}").into()),
+            }
+            channel_id
+        }
+        None => http
+            .get_guild(guild_id)
+            .await?
+            .system_channel_id
+            .ok_or_else(|| format!("Guild {guild_id} has no system channel, use --channel"))?,
+    };
+
+    channel_id
+        .send_message(http, CreateMessage::new().content(message).tts(tts))
+        .await?;
+
+    tracing::info!(
+        "Announcement posted to channel {} in guild {}",
+        channel_id,
+        guild_id
+    );
+    Ok(())
+}
@@ -0,0 +1,234 @@
+use serenity::all::{
+    ApplicationFlags, ChannelType, GatewayIntents, Guild, GuildChannel, Member, Permissions,
+};
+use std::fmt;
+
+/// Permissions needed in a voice channel to play audio.
+pub const VOICE_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::CONNECT)
+    .union(Permissions::SPEAK);
+
+/// Permissions needed in a text channel to respond.
+pub const TEXT_PERMISSIONS: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::SEND_MESSAGES)
+    .union(Permissions::EMBED_LINKS);
+
+/// Intents the bot can't work without: guilds, and voice states to find users' channels.
+pub const REQUIRED_INTENTS: GatewayIntents =
+    GatewayIntents::GUILDS.union(GatewayIntents::GUILD_VOICE_STATES);
+
+/// Privileged intents, with the application flags granting them in full and until the
+/// bot is in 100 servers, and their name in the Developer Portal.
+const PRIVILEGED_INTENTS: [(GatewayIntents, ApplicationFlags, ApplicationFlags, &str); 3] = [
+    (
+        GatewayIntents::MESSAGE_CONTENT,
+        ApplicationFlags::GATEWAY_MESSAGE_CONTENT,
+        ApplicationFlags::GATEWAY_MESSAGE_CONTENT_LIMITED,
+        "Message Content",
+    ),
+    (
+        GatewayIntents::GUILD_MEMBERS,
+        ApplicationFlags::GATEWAY_GUILD_MEMBERS,
+        ApplicationFlags::GATEWAY_GUILD_MEMBERS_LIMITED,
+        "Server Members",
+    ),
+    (
+        GatewayIntents::GUILD_PRESENCES,
+        ApplicationFlags::GATEWAY_PRESENCE,
+        ApplicationFlags::GATEWAY_PRESENCE_LIMITED,
+        "Presence",
+    ),
+];
+
+/// Check the gateway intents the bot asked for against what it needs and what the
+/// application's `flags` grant, and describe each problem.
+pub fn audit_intents(requested: GatewayIntents, flags: ApplicationFlags) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !requested.contains(REQUIRED_INTENTS) {
+        warnings.push(
+            "Not requesting the Guilds and Guild Voice States intents; users' voice \
+             channels can't be found"
+                .to_string(),
+        );
+    }
+    for (intent, full, limited, name) in PRIVILEGED_INTENTS {
+        if !requested.contains(intent) || flags.contains(full) {
+            continue;
+        }
+        warnings.push(if flags.contains(limited) {
+            format!(
+                "The {name} intent is only granted until the bot is in 100 servers; \
+                 apply for it in the Developer Portal before then"
+            )
+        } else {
+            format!(
+                "The {name} intent is not enabled; turn it on under Bot > Privileged \
+                 Gateway Intents in the Developer Portal"
+            )
+        });
+    }
+    warnings
+}
+
+/// Permissions the bot lacks in one channel.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Missing {
+    pub channel: String,
+    pub permissions: Permissions,
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Missing {} in #{}", self.permissions, self.channel)
+    }
+}
+
+/// Check the bot's permissions in a guild and list every channel missing some.
+/// Voice channels are checked for playback and the system channel for posting.
+pub fn audit_guild(guild: &Guild, member: &Member) -> Vec<Missing> {
+    let mut channels: Vec<&GuildChannel> = guild.channels.values().collect();
+    channels.sort_by_key(|channel| (channel.position, channel.id));
+
+    let mut warnings = Vec::new();
+    for channel in channels {
+        let required = match channel.kind {
+            ChannelType::Voice | ChannelType::Stage => VOICE_PERMISSIONS,
+            _ if Some(channel.id) == guild.system_channel_id => TEXT_PERMISSIONS,
+            _ => continue,
+        };
+
+        let missing = required - guild.user_permissions_in(channel, member);
+        if !missing.is_empty() {
+            warnings.push(Missing {
+                channel: channel.name.clone(),
+                permissions: missing,
+            });
+        }
+    }
+
+    warnings
+}
+
+/// One line summing up an audit, so big guilds don't flood the log with a line per
+/// channel; `None` when nothing is missing.
+pub fn summary(warnings: &[Missing]) -> Option<String> {
+    match warnings {
+        [] => None,
+        [warning] => Some(warning.to_string()),
+        _ => {
+            let permissions = warnings.iter().fold(Permissions::empty(), |all, warning| {
+                all | warning.permissions
+            });
+            Some(format!(
+                "Missing {} in {} channels",
+                permissions,
+                warnings.len()
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serenity::all::{ChannelId, GuildId, Role, RoleId, UserId};
+
+    fn guild_with_everyone(permissions: Permissions) -> Guild {
+        let mut guild = Guild::default();
+        guild.id = GuildId::new(1);
+        guild.owner_id = UserId::new(99);
+
+        let mut everyone = Role::default();
+        everyone.id = RoleId::new(1);
+        everyone.guild_id = guild.id;
+        everyone.permissions = permissions;
+        guild.roles.insert(everyone.id, everyone);
+
+        guild
+    }
+
+    fn add_channel(guild: &mut Guild, id: u64, name: &str, kind: ChannelType) {
+        let mut channel = GuildChannel::default();
+        channel.id = ChannelId::new(id);
+        channel.guild_id = guild.id;
+        channel.name = name.to_string();
+        channel.kind = kind;
+        channel.position = id as u16;
+        guild.channels.insert(channel.id, channel);
+    }
+
+    fn bot_member() -> Member {
+        let mut member = Member::default();
+        member.user.id = UserId::new(2);
+        member
+    }
+
+    #[test]
+    fn test_audit_intents_all_granted() {
+        let requested = REQUIRED_INTENTS | GatewayIntents::MESSAGE_CONTENT;
+        assert!(audit_intents(requested, ApplicationFlags::GATEWAY_MESSAGE_CONTENT).is_empty());
+    }
+
+    #[test]
+    fn test_audit_intents_reports_problems() {
+        let requested = GatewayIntents::GUILDS
+            | GatewayIntents::MESSAGE_CONTENT
+            | GatewayIntents::GUILD_MEMBERS;
+        let warnings = audit_intents(requested, ApplicationFlags::GATEWAY_MESSAGE_CONTENT_LIMITED);
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("Guild Voice States"));
+        assert!(warnings[1].starts_with("The Message Content intent is only granted"));
+        assert!(warnings[2].starts_with("The Server Members intent is not enabled"));
+    }
+
+    #[test]
+    fn test_audit_guild_all_permissions_granted() {
+        let mut guild = guild_with_everyone(VOICE_PERMISSIONS | TEXT_PERMISSIONS);
+        add_channel(&mut guild, 10, "general", ChannelType::Text);
+        add_channel(&mut guild, 11, "music", ChannelType::Voice);
+        guild.system_channel_id = Some(ChannelId::new(10));
+
+        assert!(audit_guild(&guild, &bot_member()).is_empty());
+        assert_eq!(summary(&[]), None);
+    }
+
+    #[test]
+    fn test_audit_guild_reports_missing_voice_permissions() {
+        let mut guild = guild_with_everyone(Permissions::VIEW_CHANNEL | Permissions::CONNECT);
+        add_channel(&mut guild, 11, "music", ChannelType::Voice);
+        add_channel(&mut guild, 12, "stage", ChannelType::Stage);
+
+        let warnings = audit_guild(&guild, &bot_member());
+        assert_eq!(
+            warnings.iter().map(Missing::to_string).collect::<Vec<_>>(),
+            vec!["Missing Speak in #music", "Missing Speak in #stage"]
+        );
+        assert_eq!(
+            summary(&warnings).as_deref(),
+            Some("Missing Speak in 2 channels")
+        );
+    }
+
+    #[test]
+    fn test_audit_guild_checks_system_channel_only() {
+        let mut guild = guild_with_everyone(Permissions::VIEW_CHANNEL);
+        add_channel(&mut guild, 10, "general", ChannelType::Text);
+        add_channel(&mut guild, 13, "off-topic", ChannelType::Text);
+        guild.system_channel_id = Some(ChannelId::new(10));
+
+        let warnings = audit_guild(&guild, &bot_member());
+        assert_eq!(
+            summary(&warnings).as_deref(),
+            Some("Missing Embed Links and Send Messages in #general")
+        );
+    }
+
+    #[test]
+    fn test_audit_guild_owner_has_everything() {
+        let mut guild = guild_with_everyone(Permissions::empty());
+        add_channel(&mut guild, 11, "music", ChannelType::Voice);
+        guild.owner_id = UserId::new(2);
+
+        assert!(audit_guild(&guild, &bot_member()).is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use serenity::all::GatewayIntents;
+use serenity::cache::Settings;
+use std::time::Duration;
+
+use crate::config::CacheConfig;
+
+/// Translate the `[cache]` section into serenity cache settings.
+pub fn settings(config: &CacheConfig) -> Settings {
+    let mut settings = Settings::default();
+    settings.max_messages = config.max_messages;
+    settings.time_to_live = Duration::from_secs(config.time_to_live);
+    settings.cache_guilds = config.guilds;
+    settings.cache_channels = config.channels;
+    settings.cache_users = config.users;
+    settings
+}
+
+/// Additional gateway intents needed to populate the configured caches.
+pub fn intents(config: &CacheConfig) -> GatewayIntents {
+    let mut intents = GatewayIntents::empty();
+    if config.members {
+        intents |= GatewayIntents::GUILD_MEMBERS;
+    }
+    if config.presences {
+        intents |= GatewayIntents::GUILD_PRESENCES;
+    }
+    intents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_from_config() {
+        let config = CacheConfig {
+            max_messages: 25,
+            time_to_live: 60,
+            channels: false,
+            ..Default::default()
+        };
+        let settings = settings(&config);
+
+        assert_eq!(settings.max_messages, 25);
+        assert_eq!(settings.time_to_live, Duration::from_secs(60));
+        assert!(settings.cache_guilds);
+        assert!(!settings.cache_channels);
+        assert!(settings.cache_users);
+    }
+
+    #[test]
+    fn test_intents_default_requests_nothing_privileged() {
+        assert!(intents(&CacheConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_intents_members_and_presences() {
+        let config = CacheConfig {
+            members: true,
+            presences: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            intents(&config),
+            GatewayIntents::GUILD_MEMBERS | GatewayIntents::GUILD_PRESENCES
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Deleting the bot's own public messages once they are stale, following `[cleanup]`.
+//!
+//! Messages are scheduled as they are sent and deleted by one background task, one at a
+//! time with a pause in between, so a batch of expiring messages never competes with
+//! fresh replies for the rate limit. The schedule is kept in memory: messages still
+//! waiting when the bot restarts stay up.
+
+use serenity::all::{
+    ChannelId, CommandInteraction, Context, GuildId, Message, MessageFlags, MessageId,
+};
+use serenity::http::Http;
+use serenity::prelude::TypeMapKey;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+use crate::config::{CleanupConfig, CleanupPolicy};
+use crate::dedupe::RecentIds;
+
+/// Pause between two deletions.
+const DELETE_SPACING: Duration = Duration::from_secs(1);
+/// Messages remembered as already classified, so a later, more general kind can't claim
+/// them.
+const CLASSIFIED: usize = 1024;
+
+/// What a message is, which decides how long it stays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    NowPlaying,
+    Reply,
+    Error,
+}
+
+/// When to delete a message, or `None` to keep it.
+type Entry = (Option<Instant>, ChannelId, MessageId);
+
+/// Handle for scheduling deletions, kept in the client's data.
+#[derive(Debug, Clone)]
+pub struct Janitor {
+    config: Arc<CleanupConfig>,
+    sender: mpsc::UnboundedSender<Entry>,
+}
+
+impl TypeMapKey for Janitor {
+    type Value = Janitor;
+}
+
+impl Janitor {
+    /// A janitor and the receiving end to hand to [`spawn`].
+    pub fn new(config: CleanupConfig) -> (Self, mpsc::UnboundedReceiver<Entry>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let janitor = Self {
+            config: Arc::new(config),
+            sender,
+        };
+        (janitor, receiver)
+    }
+
+    fn schedule(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        kind: Kind,
+    ) {
+        let at = keep(&self.config, guild_id, kind).map(|keep| Instant::now() + keep);
+        // Only fails once the deleting task is gone, at shutdown
+        let _ = self.sender.send((at, channel_id, message_id));
+    }
+}
+
+/// How long a message of `kind` stays in a guild, or `None` to keep it.
+fn keep(config: &CleanupConfig, guild_id: GuildId, kind: Kind) -> Option<Duration> {
+    let pick = |policy: &CleanupPolicy| match kind {
+        Kind::NowPlaying => policy.now_playing,
+        Kind::Reply => policy.replies,
+        Kind::Error => policy.errors,
+    };
+    config
+        .guilds
+        .get(&guild_id.to_string())
+        .and_then(pick)
+        .or_else(|| pick(&config.policy))
+        .map(Duration::from_secs)
+}
+
+async fn janitor(ctx: &Context) -> Option<Janitor> {
+    ctx.data.read().await.get::<Janitor>().cloned()
+}
+
+/// Schedule the deletion of a message the bot sent in a guild. The first kind a message
+/// is scheduled as decides, even when that kind is kept.
+pub async fn schedule(ctx: &Context, guild_id: Option<GuildId>, message: &Message, kind: Kind) {
+    let is_ephemeral = message
+        .flags
+        .is_some_and(|flags| flags.contains(MessageFlags::EPHEMERAL));
+    if let (Some(guild_id), Some(janitor), false) = (guild_id, janitor(ctx).await, is_ephemeral) {
+        janitor.schedule(guild_id, message.channel_id, message.id, kind);
+    }
+}
+
+/// Schedule the deletion of a command's response, looking it up only when the guild's
+/// policy would delete it.
+pub async fn schedule_response(ctx: &Context, command: &CommandInteraction, kind: Kind) {
+    let (Some(guild_id), Some(janitor)) = (command.guild_id, janitor(ctx).await) else {
+        return;
+    };
+    if keep(&janitor.config, guild_id, kind).is_none() {
+        return;
+    }
+    match command.get_response(&ctx.http).await {
+        Ok(message) => schedule(ctx, Some(guild_id), &message, kind).await,
+        Err(e) => tracing::debug!("No response to clean up for /{}: {}", command.data.name, e),
+    }
+}
+
+/// Delete scheduled messages as they fall due.
+pub fn spawn(http: Arc<Http>, mut receiver: mpsc::UnboundedReceiver<Entry>) {
+    tokio::spawn(async move {
+        let mut schedule = Schedule::new();
+        loop {
+            let next = schedule.next_due();
+            tokio::select! {
+                entry = receiver.recv() => match entry {
+                    Some(entry) => schedule.push(entry),
+                    None => return,
+                },
+                _ = tokio::time::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                    while let Some((channel_id, message_id)) = schedule.pop_due(Instant::now()) {
+                        if let Err(e) = channel_id.delete_message(&http, message_id).await {
+                            // Usually someone deleted it first
+                            tracing::debug!(
+                                "Could not delete message {} in channel {}: {}",
+                                message_id,
+                                channel_id,
+                                e
+                            );
+                        }
+                        tokio::time::sleep(DELETE_SPACING).await;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Pending deletions, soonest first, each message at most once.
+#[derive(Debug)]
+struct Schedule {
+    due: BinaryHeap<Reverse<(Instant, ChannelId, MessageId)>>,
+    classified: RecentIds,
+}
+
+impl Schedule {
+    fn new() -> Self {
+        Self {
+            due: BinaryHeap::new(),
+            classified: RecentIds::new(CLASSIFIED),
+        }
+    }
+
+    fn push(&mut self, (at, channel_id, message_id): Entry) {
+        if self.classified.insert(message_id.get())
+            && let Some(at) = at
+        {
+            self.due.push(Reverse((at, channel_id, message_id)));
+        }
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.due.peek().map(|Reverse((at, _, _))| *at)
+    }
+
+    fn pop_due(&mut self, now: Instant) -> Option<(ChannelId, MessageId)> {
+        if self.next_due()? > now {
+            return None;
+        }
+        let Reverse((_, channel_id, message_id)) = self.due.pop()?;
+        Some((channel_id, message_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn config() -> CleanupConfig {
+        CleanupConfig {
+            policy: CleanupPolicy {
+                now_playing: Some(600),
+                replies: None,
+                errors: Some(60),
+            },
+            guilds: BTreeMap::from([(
+                "2".to_string(),
+                CleanupPolicy {
+                    replies: Some(30),
+                    errors: Some(5),
+                    ..CleanupPolicy::default()
+                },
+            )]),
+        }
+    }
+
+    #[test]
+    fn test_keep() {
+        let config = config();
+        let secs = |guild, kind| keep(&config, GuildId::new(guild), kind).map(|d| d.as_secs());
+        assert_eq!(secs(1, Kind::NowPlaying), Some(600));
+        assert_eq!(secs(1, Kind::Reply), None);
+        assert_eq!(secs(1, Kind::Error), Some(60));
+        assert_eq!(secs(2, Kind::NowPlaying), Some(600));
+        assert_eq!(secs(2, Kind::Reply), Some(30));
+        assert_eq!(secs(2, Kind::Error), Some(5));
+    }
+
+    #[test]
+    fn test_schedule_order_and_dedupe() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let channel = ChannelId::new(1);
+        let mut schedule = Schedule::new();
+        schedule.push((Some(at(20)), channel, MessageId::new(2)));
+        schedule.push((Some(at(10)), channel, MessageId::new(1)));
+        schedule.push((Some(at(5)), channel, MessageId::new(2)));
+        // Kept by its first kind, so a later one can't schedule it
+        schedule.push((None, channel, MessageId::new(3)));
+        schedule.push((Some(at(1)), channel, MessageId::new(3)));
+
+        assert_eq!(schedule.next_due(), Some(at(10)));
+        assert_eq!(schedule.pop_due(at(9)), None);
+        assert_eq!(schedule.pop_due(at(15)), Some((channel, MessageId::new(1))));
+        assert_eq!(schedule.pop_due(at(15)), None);
+        assert_eq!(schedule.pop_due(at(20)), Some((channel, MessageId::new(2))));
+        assert_eq!(schedule.next_due(), None);
+    }
+}
@@ -0,0 +1,106 @@
+use serenity::all::{CommandInteraction, Context, CreateCommand, CreateInteractionResponse};
+use std::time::{Duration, Instant};
+
+use crate::config::{AboutConfig, ThemeConfig, VERSION};
+use crate::{invite, views};
+
+pub const NAME: &str = "about";
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new(NAME).description("Show version, uptime and links for this bot")
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    started: Instant,
+    config: &AboutConfig,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let invite_url = config.invite_url.clone().unwrap_or_else(|| {
+        let application_id = ctx
+            .http
+            .application_id()
+            .map(|id| id.get())
+            .unwrap_or_else(|| ctx.cache.current_user().id.get());
+        invite::invite_url(application_id)
+    });
+
+    let mut card = views::Card::new("Triboferrin")
+        .field("Version", VERSION, true)
+        .field("Uptime", format_uptime(started.elapsed()), true)
+        .field(
+            "Shard",
+            format!("{} of {}", ctx.shard_id.0 + 1, ctx.cache.shard_count()),
+            true,
+        )
+        .field("Servers", ctx.cache.guild_count().to_string(), true)
+        .field("Library", serenity_version(), true)
+        .field(
+            "Invite",
+            format!("[Add to your server]({invite_url})"),
+            false,
+        );
+    if let Some(ref support_url) = config.support_url {
+        card = card.field("Support", support_url, false);
+    }
+
+    let response = card.message(theme, command.guild_id);
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await
+}
+
+/// Serenity's version, taken from the user agent it sends to Discord.
+fn serenity_version() -> String {
+    let version = serenity::constants::USER_AGENT
+        .rsplit(", ")
+        .next()
+        .and_then(|rest| rest.strip_suffix(')'))
+        .unwrap_or("unknown");
+    format!("serenity {version}")
+}
+
+/// Format a duration as days, hours, minutes and seconds, omitting leading zero units.
+fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    let (days, hours, minutes, seconds) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m {seconds}s")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, "0s")]
+    #[case(59, "59s")]
+    #[case(61, "1m 1s")]
+    #[case(3600, "1h 0m 0s")]
+    #[case(90061, "1d 1h 1m 1s")]
+    fn test_format_uptime(#[case] secs: u64, #[case] expected: &str) {
+        assert_eq!(format_uptime(Duration::from_secs(secs)), expected);
+    }
+
+    #[test]
+    fn test_serenity_version() {
+        let version = serenity_version();
+        assert!(version.starts_with("serenity 0."));
+        assert!(!version.contains(')'));
+    }
+}
@@ -0,0 +1,129 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, Permissions,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::respond_error;
+use crate::config::ThemeConfig;
+use crate::player::Player;
+use crate::player::health::{Resolver, Summary};
+use crate::views::Card;
+use crate::{access, views};
+
+pub const NAME: &str = "admin";
+const SOURCES: &str = "sources";
+/// How long to wait for `yt-dlp --version`.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new(NAME)
+        .description("Tools for the bot's operators")
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            SOURCES,
+            "Show how each track source has been doing lately",
+        ))
+}
+
+/// Answer operator subcommands, which only the application's owners may use since they
+/// show the state of the whole bot rather than of one server.
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    match access::owners(&ctx.http).await {
+        Ok(owners) if owners.contains(&command.user.id) => {}
+        Ok(_) => {
+            return respond_error(ctx, command, "Only the bot's owners can use this.").await;
+        }
+        Err(e) => {
+            tracing::warn!("Could not look up application owners: {}", e);
+            return respond_error(ctx, command, "Could not check who owns the bot.").await;
+        }
+    }
+
+    // The only subcommand so far
+    let response = sources(player).await.message(theme, command.guild_id);
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(response.ephemeral(true)),
+        )
+        .await
+}
+
+async fn sources(player: &Player) -> Card {
+    let version = match tokio::time::timeout(VERSION_TIMEOUT, player.ytdlp().version()).await {
+        Ok(Ok(version)) => version,
+        Ok(Err(e)) => e,
+        Err(_) => "yt-dlp did not answer in time".to_string(),
+    };
+
+    let mut card = Card::new("Track sources").field("yt-dlp version", version, false);
+    for resolver in Resolver::ALL {
+        let value = if resolver == Resolver::Spotify && !player.spotify_configured() {
+            "Not configured".to_string()
+        } else {
+            describe(&player.health.summary(resolver))
+        };
+        card = card.field(resolver.name(), value, false);
+    }
+    card
+}
+
+fn describe(summary: &Summary) -> String {
+    let mut text = summary.status().to_string();
+    if summary.lookups > 0 {
+        text.push_str(&format!(
+            ": {} of the last {} lookups failed",
+            summary.failures, summary.lookups
+        ));
+    }
+    if let Some(latency) = summary.average_latency {
+        text.push_str(&format!(", {} ms on average", latency.as_millis()));
+    }
+    if let Some((ago, ref message)) = summary.last_error {
+        text.push_str(&format!(
+            "\nLast error {} ago: {}",
+            views::format_duration(ago),
+            message
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let summary = Summary {
+            lookups: 4,
+            failures: 1,
+            average_latency: Some(Duration::from_millis(1250)),
+            last_error: Some((Duration::from_secs(90), "HTTP Error 429".to_string())),
+        };
+        assert_eq!(
+            describe(&summary),
+            "Degraded: 1 of the last 4 lookups failed, 1250 ms on average\n\
+             Last error 1:30 ago: HTTP Error 429"
+        );
+    }
+
+    #[test]
+    fn test_describe_without_lookups() {
+        let summary = Summary {
+            lookups: 0,
+            failures: 0,
+            average_latency: None,
+            last_error: None,
+        };
+        assert_eq!(describe(&summary), "No lookups yet");
+    }
+}
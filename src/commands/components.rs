@@ -0,0 +1,157 @@
+//! Custom ids for the buttons on the bot's messages.
+//!
+//! An id reads `v1:<namespace>:<state>:<signature>`. The namespace picks the handler and
+//! the state carries everything it needs, so buttons on messages sent before a restart
+//! keep working. The signature is keyed by the bot token, so handlers only ever see
+//! state this bot wrote; rotating the token retires every older button.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ring::hmac;
+use serenity::all::{
+    ComponentInteraction, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+
+const VERSION: &str = "v1";
+/// Signature bytes kept in an id, to leave room for state in Discord's 100 characters.
+const SIGNATURE_LEN: usize = 12;
+/// Discord's limit on custom ids.
+const MAX_LEN: usize = 100;
+
+/// Where a component interaction goes: its handler's namespace and the state to use.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Route<'a> {
+    pub namespace: &'a str,
+    pub state: &'a str,
+}
+
+pub struct Components {
+    key: hmac::Key,
+}
+
+impl Components {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+        }
+    }
+
+    /// Custom id routing a component to `namespace` with `state`, which may contain
+    /// colons.
+    pub fn custom_id(&self, namespace: &str, state: &str) -> String {
+        let signed = format!("{VERSION}:{namespace}:{state}");
+        let custom_id = format!("{signed}:{}", self.sign(&signed));
+        debug_assert!(
+            custom_id.len() <= MAX_LEN,
+            "custom id too long: {custom_id}"
+        );
+        custom_id
+    }
+
+    /// Route for a custom id, or `None` when it is malformed, from an unknown version or
+    /// carries a signature this bot didn't make.
+    pub fn route<'a>(&self, custom_id: &'a str) -> Option<Route<'a>> {
+        let versioned = custom_id
+            .strip_prefix(VERSION)
+            .and_then(|id| id.strip_prefix(':'))?;
+
+        let (signed, signature) = custom_id.rsplit_once(':')?;
+        if !same(self.sign(signed).as_bytes(), signature.as_bytes()) {
+            return None;
+        }
+        let (namespace, state) = versioned.rsplit_once(':')?.0.split_once(':')?;
+        Some(Route { namespace, state })
+    }
+
+    fn sign(&self, signed: &str) -> String {
+        let tag = hmac::sign(&self.key, signed.as_bytes());
+        URL_SAFE_NO_PAD.encode(&tag.as_ref()[..SIGNATURE_LEN])
+    }
+}
+
+/// Compare without stopping at the first difference, so timing can't reveal a signature.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Tell the user a button can't be used any more, instead of letting the interaction fail.
+pub async fn respond_expired(
+    ctx: &Context,
+    component: &ComponentInteraction,
+) -> serenity::Result<()> {
+    respond_error(
+        ctx,
+        component,
+        "This button no longer works. Run the command again.",
+    )
+    .await
+}
+
+/// Refuse a button of a command disabled in the guild it was pressed in.
+pub async fn respond_disabled(
+    ctx: &Context,
+    component: &ComponentInteraction,
+) -> serenity::Result<()> {
+    respond_error(ctx, component, super::DISABLED_MESSAGE).await
+}
+
+/// Answer a button press with an error only the presser sees.
+pub async fn respond_error(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    message: &str,
+) -> serenity::Result<()> {
+    let response = CreateInteractionResponseMessage::new()
+        .content(message)
+        .ephemeral(true);
+    component
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_round_trip() {
+        let components = Components::new("secret");
+        let custom_id = components.custom_id("queue", "page:3");
+        assert!(custom_id.starts_with("v1:queue:page:3:"));
+        assert_eq!(
+            components.route(&custom_id),
+            Some(Route {
+                namespace: "queue",
+                state: "page:3"
+            })
+        );
+    }
+
+    #[test]
+    fn test_survives_restart() {
+        let custom_id = Components::new("secret").custom_id("queue", "page:1");
+        assert!(Components::new("secret").route(&custom_id).is_some());
+        assert_eq!(Components::new("rotated").route(&custom_id), None);
+    }
+
+    #[test]
+    fn test_tampered_state_rejected() {
+        let components = Components::new("secret");
+        let custom_id = components.custom_id("queue", "page:1");
+        let tampered = custom_id.replacen("page:1", "page:2", 1);
+        assert_eq!(components.route(&tampered), None);
+    }
+
+    #[rstest]
+    #[case("queue:page:3", None)]
+    #[case("other:page:3", None)]
+    #[case("v2:queue:page:3:abc", None)]
+    #[case("v1:queue:page:3", None)]
+    #[case("v1:", None)]
+    #[case("", None)]
+    fn test_route(#[case] custom_id: &str, #[case] expected: Option<(&str, &str)>) {
+        let expected = expected.map(|(namespace, state)| Route { namespace, state });
+        assert_eq!(Components::new("secret").route(custom_id), expected);
+    }
+}
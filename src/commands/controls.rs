@@ -0,0 +1,165 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, GuildId,
+};
+use std::sync::Arc;
+
+use crate::commands::respond_error;
+use crate::config::ThemeConfig;
+use crate::player::{LoopMode, Player};
+use crate::views::Card;
+
+pub const LOOP: &str = "loop";
+pub const PAUSE: &str = "pause";
+pub const RESUME: &str = "resume";
+pub const SKIP: &str = "skip";
+pub const STOP: &str = "stop";
+
+pub fn register() -> Vec<(&'static str, CreateCommand)> {
+    vec![
+        (
+            LOOP,
+            CreateCommand::new(LOOP)
+                .description("Repeat the current track or the whole queue")
+                .add_option(
+                    LoopMode::ALL.into_iter().fold(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "mode",
+                            "What to repeat, or off to play the queue once",
+                        )
+                        .required(true),
+                        |option, mode| option.add_string_choice(mode.name(), mode.name()),
+                    ),
+                ),
+        ),
+        (
+            PAUSE,
+            CreateCommand::new(PAUSE).description("Pause the current track"),
+        ),
+        (
+            RESUME,
+            CreateCommand::new(RESUME).description("Resume the paused track"),
+        ),
+        (
+            SKIP,
+            CreateCommand::new(SKIP)
+                .description("Skip to the next queued track")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "count",
+                        "How many tracks to skip, counting the current one",
+                    )
+                    .min_int_value(1),
+                ),
+        ),
+        (
+            STOP,
+            CreateCommand::new(STOP)
+                .description("Stop playback, clear the queue and leave the voice channel"),
+        ),
+    ]
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_error(ctx, command, "Music can only be controlled in a server.").await;
+    };
+
+    let card = match command.data.name.as_str() {
+        LOOP => match loop_mode(command) {
+            Some(mode) => {
+                player.set_loop_mode(guild_id, mode);
+                Ok(Card::new("Loop").field("Mode", mode.name(), true))
+            }
+            None => Err("Pick track, queue or off.".to_string()),
+        },
+        PAUSE => player.pause(guild_id).map(|track| {
+            with_loop(
+                Card::new("Paused").field("Track", track.title, false),
+                player,
+                guild_id,
+            )
+        }),
+        RESUME => player.resume(guild_id).map(|track| {
+            with_loop(
+                Card::new("Resumed").field("Track", track.title, false),
+                player,
+                guild_id,
+            )
+        }),
+        SKIP => player
+            .skip(guild_id, skip_count(command))
+            .map(|(skipped, dropped, next)| {
+                let card = Card::new("Skipped").field("Track", skipped.title, false);
+                let card = match dropped {
+                    0 => card,
+                    _ => card.field("Also skipped", format!("{dropped} queued"), true),
+                };
+                card.field(
+                    "Up next",
+                    next.map_or_else(
+                        || "Nothing, the queue is empty".to_string(),
+                        |next| next.title,
+                    ),
+                    false,
+                )
+            }),
+        _ => {
+            let (stopped, cleared) = player.stop(ctx, guild_id).await;
+            let card = match stopped {
+                Some(track) => Card::new("Stopped").field("Track", track.title, false),
+                None => Card::new("Left the voice channel"),
+            };
+            Ok(card.field("Cleared from queue", cleared.to_string(), true))
+        }
+    };
+
+    match card {
+        Ok(card) => {
+            let response = card.message(theme, Some(guild_id));
+            command
+                .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+                .await
+        }
+        Err(e) => respond_error(ctx, command, &e).await,
+    }
+}
+
+/// Mention the loop mode next to the current track, unless looping is off.
+pub fn with_loop(card: Card, player: &Player, guild_id: GuildId) -> Card {
+    match player.loop_mode(guild_id) {
+        LoopMode::Off => card,
+        mode => card.field("Loop", mode.name(), true),
+    }
+}
+
+/// The mode picked for `/loop`.
+fn loop_mode(command: &CommandInteraction) -> Option<LoopMode> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "mode")
+        .and_then(|option| option.value.as_str())
+        .and_then(LoopMode::from_name)
+}
+
+/// Tracks `/skip` should skip, one unless `count` says otherwise.
+fn skip_count(command: &CommandInteraction) -> usize {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "count")
+        .and_then(|option| option.value.as_i64())
+        .and_then(|count| usize::try_from(count).ok())
+        .unwrap_or(1)
+        .max(1)
+}
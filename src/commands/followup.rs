@@ -0,0 +1,167 @@
+use serenity::all::{
+    CommandInteraction, Context, CreateMessage, EditInteractionResponse, Mentionable, MessageFlags,
+    Timestamp,
+};
+use std::time::Duration;
+
+use crate::cleanup::{self, Kind};
+use crate::config::ThemeConfig;
+use crate::views::Card;
+
+/// Interaction tokens expire 15 minutes after the interaction was created. A minute is
+/// kept in reserve so a request started just before expiry still lands.
+const TOKEN_LIFETIME_SECS: i64 = 14 * 60;
+
+/// Whether an interaction created at `created` (unix seconds) can still edit its response at `now`.
+fn token_valid(created: i64, now: i64) -> bool {
+    now - created < TOKEN_LIFETIME_SECS
+}
+
+/// How long the interaction token of a command stays usable from `now` (unix seconds).
+fn token_remaining(created: i64, now: i64) -> Duration {
+    Duration::from_secs((TOKEN_LIFETIME_SECS - (now - created)).max(0) as u64)
+}
+
+/// How much longer a deferred command may run once its `timeout` is up, at `now`: until
+/// its token expires, and `timeout` past that so a result arriving late still reaches the
+/// channel.
+fn deferred_extension(created: i64, now: i64, timeout: Duration) -> Duration {
+    token_remaining(created, now) + timeout
+}
+
+/// Extra running time for a command whose timeout is up, when it deferred its response
+/// and is still working on it; `None` for anything else.
+pub async fn extension(
+    ctx: &Context,
+    command: &CommandInteraction,
+    timeout: Duration,
+) -> Option<Duration> {
+    let response = command.get_response(&ctx.http).await.ok()?;
+    let thinking = response
+        .flags
+        .is_some_and(|flags| flags.contains(MessageFlags::LOADING));
+    thinking.then(|| {
+        deferred_extension(
+            command.id.created_at().unix_timestamp(),
+            Timestamp::now().unix_timestamp(),
+            timeout,
+        )
+    })
+}
+
+/// Deliver a late result for a deferred or answered command. Edits the original response
+/// while the interaction token is valid and posts to the channel, mentioning the user,
+/// once it has expired. The message is cleaned up later as a `kind` message.
+pub async fn send(
+    ctx: &Context,
+    command: &CommandInteraction,
+    kind: Kind,
+    content: &str,
+) -> serenity::Result<()> {
+    let edit = || EditInteractionResponse::new().content(content);
+    let message = |mention: &str| CreateMessage::new().content(format!("{mention} {content}"));
+    deliver(ctx, command, kind, edit, message).await
+}
+
+/// Deliver a late result as a card, the way `send` does with text.
+pub async fn send_card(
+    ctx: &Context,
+    command: &CommandInteraction,
+    kind: Kind,
+    card: Card,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let guild_id = command.guild_id;
+    let edit = || card.clone().edit(theme, guild_id);
+    let message = |mention: &str| card.clone().channel_message(theme, guild_id, mention);
+    deliver(ctx, command, kind, edit, message).await
+}
+
+async fn deliver(
+    ctx: &Context,
+    command: &CommandInteraction,
+    kind: Kind,
+    edit: impl FnOnce() -> EditInteractionResponse,
+    message: impl FnOnce(&str) -> CreateMessage,
+) -> serenity::Result<()> {
+    let created = command.id.created_at().unix_timestamp();
+    let message = if token_valid(created, Timestamp::now().unix_timestamp()) {
+        command.edit_response(&ctx.http, edit()).await?
+    } else {
+        tracing::debug!(
+            "Interaction token for /{} expired, replying in channel {}",
+            command.data.name,
+            command.channel_id
+        );
+        let message = message(&command.user.id.mention().to_string());
+        command.channel_id.send_message(&ctx.http, message).await?
+    };
+    cleanup::schedule(ctx, command.guild_id, &message, kind).await;
+    Ok(())
+}
+
+/// Send the outcome of a deferred command: a `kind` message when it succeeded, an error
+/// otherwise.
+pub async fn reply(
+    ctx: &Context,
+    command: &CommandInteraction,
+    kind: Kind,
+    outcome: Result<String, String>,
+) -> serenity::Result<()> {
+    match outcome {
+        Ok(content) => send(ctx, command, kind, &content).await,
+        Err(e) => send(ctx, command, Kind::Error, &e).await,
+    }
+}
+
+/// Send the outcome of a deferred command as a card, the way `reply` does with text.
+pub async fn reply_card(
+    ctx: &Context,
+    command: &CommandInteraction,
+    kind: Kind,
+    outcome: Result<Card, String>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    match outcome {
+        Ok(card) => send_card(ctx, command, kind, card, theme).await,
+        Err(e) => send(ctx, command, Kind::Error, &e).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommandsConfig;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(1_000, 1_000, true)]
+    #[case(1_000, 1_000 + 13 * 60, true)]
+    #[case(1_000, 1_000 + 14 * 60, false)]
+    #[case(1_000, 1_000 + 60 * 60, false)]
+    fn test_token_valid(#[case] created: i64, #[case] now: i64, #[case] expected: bool) {
+        assert_eq!(token_valid(created, now), expected);
+    }
+
+    #[test]
+    fn test_token_remaining() {
+        assert_eq!(
+            token_remaining(1_000, 1_000 + 60),
+            Duration::from_secs(13 * 60)
+        );
+        assert_eq!(token_remaining(1_000, 1_000 + 60 * 60), Duration::ZERO);
+    }
+
+    /// With the default timeout, a deferred command outlives its token, so a late result
+    /// goes to the channel rather than being cancelled.
+    #[test]
+    fn test_deferred_command_reaches_fallback_with_default_timeout() {
+        let timeout = Duration::from_secs(CommandsConfig::default().timeout);
+        let created = 1_000;
+        let timed_out = created + timeout.as_secs() as i64;
+        let deadline = timed_out + deferred_extension(created, timed_out, timeout).as_secs() as i64;
+
+        assert!(token_valid(created, timed_out));
+        assert!(!token_valid(created, deadline - 1));
+    }
+}
@@ -0,0 +1,230 @@
+use serenity::all::{
+    ButtonStyle, CommandInteraction, CommandOptionType, ComponentInteraction, Context,
+    CreateActionRow, CreateButton, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseFollowup,
+};
+use std::sync::Arc;
+
+use crate::cleanup::{self, Kind};
+use crate::commands::components::{self, Components};
+use crate::commands::{play, respond_error};
+use crate::config::ThemeConfig;
+use crate::player::library::{self, Library};
+use crate::player::queue::QueuedTrack;
+use crate::player::{self, Player};
+use crate::storage::{Played, Storage};
+use crate::views::{self, Card};
+
+pub const NAME: &str = "history";
+const DEFAULT_COUNT: usize = 10;
+/// One re-queue button per track, and a message holds at most 5 rows of 5 buttons.
+const MAX_COUNT: usize = 25;
+const BUTTONS_PER_ROW: usize = 5;
+/// Longest title shown, so a full page fits in an embed's 4096 characters.
+const TITLE_LIMIT: usize = 80;
+/// Prefix of the re-queue buttons' state, followed by the history entry's id.
+const REQUEUE_PREFIX: &str = "play:";
+const NOT_CONFIGURED: &str = "Playback history needs a database, and none is configured.";
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new(NAME)
+        .description("Show the tracks played here recently, with buttons to play them again")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "count",
+                "How many tracks to show",
+            )
+            .min_int_value(1)
+            .max_int_value(MAX_COUNT as u64),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    storage: Option<&Storage>,
+    components: &Components,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_error(ctx, command, "History is only kept in servers.").await;
+    };
+    let Some(storage) = storage else {
+        return respond_error(ctx, command, NOT_CONFIGURED).await;
+    };
+    let count = command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "count")
+        .and_then(|option| option.value.as_i64())
+        .map_or(DEFAULT_COUNT, |count| {
+            count.clamp(1, MAX_COUNT as i64) as usize
+        });
+
+    let history = match storage.history(guild_id, count).await {
+        Ok(history) => history,
+        Err(e) => return respond_error(ctx, command, &e).await,
+    };
+    let card = Card::new("Recently played").description(describe(&history));
+    let response = card
+        .message(theme, Some(guild_id))
+        .components(buttons(&history, components));
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await
+}
+
+/// Queue the track a history button points to in the presser's voice channel.
+pub async fn requeue(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    state: &str,
+    player: &Arc<Player>,
+    storage: Option<&Storage>,
+    library: Option<&Library>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let (Some(guild_id), Some(storage), Some(play_id)) = (
+        component.guild_id,
+        storage,
+        state
+            .strip_prefix(REQUEUE_PREFIX)
+            .and_then(|id| id.parse::<i64>().ok()),
+    ) else {
+        return components::respond_expired(ctx, component).await;
+    };
+    let track = match storage.played(guild_id, play_id).await {
+        Ok(played) => QueuedTrack {
+            requester: component.user.id,
+            ..played.track
+        },
+        Err(e) => return components::respond_error(ctx, component, &e).await,
+    };
+    let Some(track) = library::playable(library, vec![track]).await.0.pop() else {
+        let message = "That file is no longer in the music library.";
+        return components::respond_error(ctx, component, message).await;
+    };
+    let Some(channel_id) = player::voice_channel(ctx, guild_id, component.user.id) else {
+        return components::respond_error(ctx, component, "Join a voice channel first.").await;
+    };
+
+    // Joining can take longer than the response window; the history message stays as is
+    component.defer(&ctx.http).await?;
+    let (followup, kind) = match player.play(ctx, guild_id, channel_id, vec![track]).await {
+        Ok(outcome) => (
+            play::describe(player, guild_id, channel_id, outcome).followup(theme, Some(guild_id)),
+            Kind::NowPlaying,
+        ),
+        Err(e) => (
+            CreateInteractionResponseFollowup::new().content(e),
+            Kind::Error,
+        ),
+    };
+    let message = component.create_followup(&ctx.http, followup).await?;
+    cleanup::schedule(ctx, Some(guild_id), &message, kind).await;
+    Ok(())
+}
+
+/// One line per play, newest first, with the time it started.
+fn describe(history: &[Played]) -> String {
+    if history.is_empty() {
+        return "Nothing has played here yet.".to_string();
+    }
+    history
+        .iter()
+        .enumerate()
+        .map(|(index, played)| {
+            let duration = played
+                .track
+                .duration
+                .map(|duration| format!(" ({})", views::format_duration(duration)))
+                .unwrap_or_default();
+            format!(
+                "{}. **{}**{} - <@{}> <t:{}:R>",
+                index + 1,
+                shorten(&played.track.title),
+                duration,
+                played.track.requester,
+                played.played_at
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn shorten(title: &str) -> String {
+    if title.chars().count() > TITLE_LIMIT {
+        let cut: String = title.chars().take(TITLE_LIMIT - 1).collect();
+        format!("{cut}…")
+    } else {
+        title.to_string()
+    }
+}
+
+/// A button per play, numbered like the lines of the description.
+fn buttons(history: &[Played], components: &Components) -> Vec<CreateActionRow> {
+    let buttons: Vec<CreateButton> = history
+        .iter()
+        .enumerate()
+        .map(|(index, played)| {
+            let state = format!("{REQUEUE_PREFIX}{}", played.id);
+            CreateButton::new(components.custom_id(NAME, &state))
+                .label(format!("Play {}", index + 1))
+                .style(ButtonStyle::Secondary)
+        })
+        .collect();
+    buttons
+        .chunks(BUTTONS_PER_ROW)
+        .map(|row| CreateActionRow::Buttons(row.to_vec()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::queue::Source;
+    use serenity::all::UserId;
+    use std::time::Duration;
+
+    fn played(id: i64, title: &str) -> Played {
+        Played {
+            id,
+            track: QueuedTrack {
+                url: format!("https://example.com/{title}"),
+                title: title.to_string(),
+                duration: Some(Duration::from_secs(185)),
+                requester: UserId::new(7),
+                source: Source::Ytdlp,
+            },
+            played_at: 1_700_000_000 + id,
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe(&[played(2, "Song"), played(1, "Other")]),
+            "1. **Song** (3:05) - <@7> <t:1700000002:R>\n\
+             2. **Other** (3:05) - <@7> <t:1700000001:R>"
+        );
+        assert_eq!(describe(&[]), "Nothing has played here yet.");
+    }
+
+    #[test]
+    fn test_full_page_fits_in_embed() {
+        let history: Vec<Played> = (0..MAX_COUNT as i64)
+            .map(|id| played(id, &"a".repeat(200)))
+            .collect();
+        assert!(describe(&history).chars().count() <= 4096);
+    }
+
+    #[test]
+    fn test_buttons_fill_rows_of_five() {
+        let components = Components::new("secret");
+        let history: Vec<Played> = (0..12).map(|id| played(id, "Song")).collect();
+        assert_eq!(buttons(&history, &components).len(), 3);
+        assert!(buttons(&[], &components).is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+use serenity::all::{
+    AutocompleteChoice, CommandInteraction, CommandOptionType, Context, CreateAutocompleteResponse,
+    CreateCommand, CreateCommandOption, CreateInteractionResponse, ResolvedOption, ResolvedValue,
+};
+use std::sync::Arc;
+
+use crate::cleanup::Kind;
+use crate::commands::{followup, play, respond_error};
+use crate::config::ThemeConfig;
+use crate::player::library::{self, Library, Listing};
+use crate::player::queue::{QueuedTrack, Source};
+use crate::player::{self, Player};
+use crate::views::Card;
+
+pub const NAME: &str = "library";
+const BROWSE: &str = "browse";
+const PLAY: &str = "play";
+/// Entries shown when browsing a folder.
+const BROWSE_LIMIT: usize = 25;
+/// Longest entry name shown when browsing, so a listing fits in one message.
+const NAME_LIMIT: usize = 80;
+/// Discord allows at most 25 suggestions of up to 100 characters.
+const AUTOCOMPLETE_RESULTS: usize = 25;
+const CHOICE_MAX_LEN: usize = 100;
+const NOT_CONFIGURED: &str = "No music library is configured.";
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new(NAME)
+        .description("Browse and play the bot's local music library")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                BROWSE,
+                "List a folder of the library",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "folder",
+                "Folder to list, e.g. Artist/Album",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                PLAY,
+                "Play a file from the library in your voice channel",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "file", "File to play")
+                    .required(true)
+                    .set_autocomplete(true),
+            ),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    library: Option<&Library>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_error(ctx, command, "The library can only be used in a server.").await;
+    };
+    let Some(library) = library else {
+        return respond_error(ctx, command, NOT_CONFIGURED).await;
+    };
+
+    let options = command.data.options();
+    let Some(ResolvedOption {
+        name: subcommand,
+        value: ResolvedValue::SubCommand(options),
+        ..
+    }) = options.first()
+    else {
+        return respond_error(ctx, command, "Pick browse or play.").await;
+    };
+    let text = |name: &str| {
+        options
+            .iter()
+            .find(|option| option.name == name)
+            .and_then(|option| match option.value {
+                ResolvedValue::String(value) => Some(value),
+                _ => None,
+            })
+            .unwrap_or_default()
+    };
+
+    if *subcommand == BROWSE {
+        let folder = text("folder");
+        return match library.list(folder).await {
+            Ok(listing) => {
+                let response = browse_card(folder, &listing).message(theme, Some(guild_id));
+                command
+                    .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+                    .await
+            }
+            Err(e) => respond_error(ctx, command, &e).await,
+        };
+    }
+
+    let path = match library.file(text("file")).await {
+        Ok(path) => path,
+        Err(e) => return respond_error(ctx, command, &e).await,
+    };
+    let Some(channel_id) = player::voice_channel(ctx, guild_id, command.user.id) else {
+        return respond_error(ctx, command, "Join a voice channel first.").await;
+    };
+
+    command.defer(&ctx.http).await?;
+    let track = QueuedTrack {
+        url: path.display().to_string(),
+        title: library::title(&path),
+        duration: None,
+        requester: command.user.id,
+        source: Source::File,
+    };
+    let outcome = play::enqueue_tracks(ctx, command, player, channel_id, Ok(vec![track])).await;
+    followup::reply_card(ctx, command, Kind::NowPlaying, outcome, theme).await
+}
+
+/// Suggest library files matching what has been typed for `/library play`.
+pub async fn autocomplete(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    library: Option<&Library>,
+) -> serenity::Result<()> {
+    let typed = interaction
+        .data
+        .autocomplete()
+        .map(|option| option.value.trim().to_string())
+        .unwrap_or_default();
+
+    let mut choices = Vec::new();
+    if let Some(library) = library {
+        choices = library
+            .search(&typed, AUTOCOMPLETE_RESULTS * 2)
+            .await
+            .into_iter()
+            .filter(|path| path.len() <= CHOICE_MAX_LEN)
+            .take(AUTOCOMPLETE_RESULTS)
+            .map(|path| AutocompleteChoice::new(path.clone(), path))
+            .collect();
+    }
+
+    let response = CreateAutocompleteResponse::new().set_choices(choices);
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+        .await
+}
+
+fn browse_card(folder: &str, listing: &Listing) -> Card {
+    let folder = folder.trim().trim_matches('/');
+    Card::new(format!("Music library: /{folder}"))
+        .description(describe(listing))
+        .field("Folders", listing.folders.len().to_string(), true)
+        .field("Files", listing.files.len().to_string(), true)
+}
+
+/// One line per entry, folders first, each shown by its own name.
+fn describe(listing: &Listing) -> String {
+    let entries: Vec<String> = listing
+        .folders
+        .iter()
+        .map(|path| format!("{}/", entry_name(path)))
+        .chain(listing.files.iter().map(|path| entry_name(path)))
+        .collect();
+
+    let mut description = entries
+        .iter()
+        .take(BROWSE_LIMIT)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if entries.len() > BROWSE_LIMIT {
+        description.push_str(&format!("\n… and {} more", entries.len() - BROWSE_LIMIT));
+    }
+    if entries.is_empty() {
+        description = "This folder has no folders or audio files.".to_string();
+    }
+    description
+}
+
+/// Last part of a library path, shortened to fit a listing.
+fn entry_name(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if name.chars().count() > NAME_LIMIT {
+        let cut: String = name.chars().take(NAME_LIMIT - 1).collect();
+        format!("{cut}…")
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_name() {
+        assert_eq!(entry_name("Artist/Album/01 Song.mp3"), "01 Song.mp3");
+        assert_eq!(entry_name("Loose.mp3"), "Loose.mp3");
+        assert_eq!(entry_name(&"a".repeat(100)).chars().count(), NAME_LIMIT);
+    }
+
+    #[test]
+    fn test_describe() {
+        let listing = Listing {
+            folders: vec!["Artist/Album".to_string()],
+            files: vec!["Artist/intro.mp3".to_string()],
+        };
+        assert_eq!(describe(&listing), "Album/\nintro.mp3");
+        assert_eq!(
+            describe(&Listing::default()),
+            "This folder has no folders or audio files."
+        );
+    }
+
+    #[test]
+    fn test_describe_long_folder() {
+        let listing = Listing {
+            folders: vec![],
+            files: (0..30).map(|n| format!("{n:02}.mp3")).collect(),
+        };
+        let description = describe(&listing);
+        assert_eq!(description.lines().count(), BROWSE_LIMIT + 1);
+        assert!(description.ends_with("… and 5 more"));
+    }
+}
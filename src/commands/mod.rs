@@ -0,0 +1,436 @@
+pub mod about;
+pub mod admin;
+pub mod components;
+pub mod controls;
+pub mod followup;
+pub mod history;
+pub mod library;
+pub mod play;
+pub mod playlist;
+pub mod queue;
+pub mod remove;
+pub mod search;
+pub mod summon;
+
+use futures::FutureExt;
+use serenity::all::{
+    Command, CommandInteraction, Context, CreateAutocompleteResponse, CreateCommand,
+    CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, Timestamp,
+};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+use tokio::time::error::Elapsed;
+
+use crate::cleanup::Kind;
+use crate::config::CommandsConfig;
+
+const TIMED_OUT_MESSAGE: &str = "This command took too long and was cancelled.";
+const PANICKED_MESSAGE: &str = "Something went wrong while running this command.";
+const DISABLED_MESSAGE: &str = "This command is disabled in this server.";
+
+/// Every slash command the bot provides, by name.
+fn definitions() -> Vec<(&'static str, CreateCommand)> {
+    let mut commands = vec![
+        (about::NAME, about::register()),
+        (admin::NAME, admin::register()),
+        (history::NAME, history::register()),
+        (library::NAME, library::register()),
+        (play::NAME, play::register()),
+        (playlist::NAME, playlist::register()),
+        (queue::NAME, queue::register()),
+        (remove::NAME, remove::register()),
+        (search::NAME, search::register()),
+    ];
+    commands.extend(controls::register());
+    commands.extend(summon::register());
+    commands
+}
+
+/// Groups of commands that can be disabled together by naming the group.
+const CATEGORIES: [(&str, &[&str]); 5] = [
+    (
+        "playback",
+        &[
+            play::NAME,
+            search::NAME,
+            queue::NAME,
+            remove::NAME,
+            controls::LOOP,
+            controls::PAUSE,
+            controls::RESUME,
+            controls::SKIP,
+            controls::STOP,
+            summon::SUMMON,
+            summon::MOVETO,
+        ],
+    ),
+    ("playlists", &[playlist::NAME, history::NAME]),
+    ("library", &[library::NAME]),
+    ("info", &[about::NAME]),
+    ("admin", &[admin::NAME]),
+];
+
+/// Whether a `disabled` list, of command and category names, covers command `name`.
+fn covers(disabled: &[String], name: &str) -> bool {
+    disabled.iter().any(|entry| {
+        entry == name
+            || CATEGORIES
+                .iter()
+                .any(|(category, names)| category == entry && names.contains(&name))
+    })
+}
+
+/// Whether some guild disables command `name`, so it is registered per guild.
+fn disabled_in_a_guild(config: &CommandsConfig, name: &str) -> bool {
+    config
+        .guilds
+        .values()
+        .any(|disabled| covers(disabled, name))
+}
+
+/// Slash commands registered anywhere, leaving out globally disabled ones.
+pub fn all(config: &CommandsConfig) -> Vec<CreateCommand> {
+    definitions()
+        .into_iter()
+        .filter(|(name, _)| !covers(&config.disabled, name))
+        .map(|(_, command)| command)
+        .collect()
+}
+
+/// Slash commands every guild gets: those no guild disables.
+fn global(config: &CommandsConfig) -> Vec<(&'static str, CreateCommand)> {
+    definitions()
+        .into_iter()
+        .filter(|(name, _)| !covers(&config.disabled, name) && !disabled_in_a_guild(config, name))
+        .collect()
+}
+
+/// Slash commands registered in one guild only: those some guilds disable, but this one
+/// doesn't. Together with the global ones they make up the guild's command list.
+fn for_guild(config: &CommandsConfig, guild_id: GuildId) -> Vec<(&'static str, CreateCommand)> {
+    definitions()
+        .into_iter()
+        .filter(|(name, _)| {
+            !covers(&config.disabled, name)
+                && disabled_in_a_guild(config, name)
+                && is_enabled(config, Some(guild_id), name)
+        })
+        .collect()
+}
+
+/// Whether a command may run in a guild (or in DMs, when `guild_id` is `None`).
+pub fn is_enabled(config: &CommandsConfig, guild_id: Option<GuildId>, name: &str) -> bool {
+    if covers(&config.disabled, name) {
+        return false;
+    }
+    guild_id
+        .and_then(|guild_id| config.guilds.get(&guild_id.to_string()))
+        .is_none_or(|disabled| !covers(disabled, name))
+}
+
+/// Refuse a command disabled in the guild it was used in.
+pub async fn respond_disabled(ctx: &Context, command: &CommandInteraction) -> serenity::Result<()> {
+    let response = CreateInteractionResponseMessage::new()
+        .content(DISABLED_MESSAGE)
+        .ephemeral(true);
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await
+}
+
+/// Offer no suggestions for a command disabled in the guild it is typed in.
+pub async fn autocomplete_disabled(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+) -> serenity::Result<()> {
+    let response = CreateAutocompleteResponse::new();
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+        .await
+}
+
+/// Replace the bot's global slash commands with the current set.
+pub async fn register(ctx: &Context, config: &CommandsConfig) -> serenity::Result<()> {
+    let commands = global(config)
+        .into_iter()
+        .map(|(_, command)| command)
+        .collect();
+    let commands = Command::set_global_commands(&ctx.http, commands).await?;
+    tracing::info!("Registered {} slash commands", commands.len());
+    Ok(())
+}
+
+/// Replace a guild's own slash commands with the ones only some guilds get, so commands
+/// disabled there are missing from its command list. Guilds get an empty set when no
+/// guild disables anything, clearing what an earlier configuration registered.
+pub async fn register_guild(
+    ctx: &Context,
+    config: &CommandsConfig,
+    guild_id: GuildId,
+) -> serenity::Result<()> {
+    let commands = for_guild(config, guild_id)
+        .into_iter()
+        .map(|(_, command)| command)
+        .collect();
+    let commands = guild_id.set_commands(&ctx.http, commands).await?;
+    tracing::debug!(
+        "Registered {} slash commands in guild {}",
+        commands.len(),
+        guild_id
+    );
+    Ok(())
+}
+
+/// Run a command handler, dropping it (and any request it awaits) once `timeout`
+/// elapses or it panics, and telling the user instead of leaving the interaction
+/// "thinking". Deferred commands still working, such as big playlist imports, get until
+/// their interaction token expires and another `timeout`, with late results posted to the
+/// channel. A zero timeout runs the handler without a limit.
+pub async fn run_guarded(
+    ctx: &Context,
+    command: &CommandInteraction,
+    timeout: Duration,
+    handler: impl Future<Output = serenity::Result<()>>,
+) -> serenity::Result<()> {
+    let extend = async || followup::extension(ctx, command, timeout).await;
+    match guard(handler, timeout, extend).await {
+        Ok(Ok(result)) => result,
+        // Already logged by the panic hook
+        Ok(Err(_)) => respond_error(ctx, command, PANICKED_MESSAGE).await,
+        Err(_) => {
+            tracing::warn!(
+                "Command /{} cancelled after {}s",
+                command.data.name,
+                command
+                    .id
+                    .created_at()
+                    .unix_timestamp()
+                    .abs_diff(Timestamp::now().unix_timestamp())
+            );
+            respond_error(ctx, command, TIMED_OUT_MESSAGE).await
+        }
+    }
+}
+
+/// Wait for `handler` for up to `timeout` and then however long `extend` allows, catching
+/// panics. A handler that runs out is dropped, which cancels what it awaits: requests,
+/// and yt-dlp runs, which are all `kill_on_drop`.
+async fn guard<T>(
+    handler: impl Future<Output = T>,
+    timeout: Duration,
+    extend: impl AsyncFnOnce() -> Option<Duration>,
+) -> Result<std::thread::Result<T>, Elapsed> {
+    let handler = AssertUnwindSafe(handler).catch_unwind();
+    if timeout.is_zero() {
+        return Ok(handler.await);
+    }
+    let mut handler = std::pin::pin!(handler);
+    match tokio::time::timeout(timeout, &mut handler).await {
+        Err(elapsed) => match extend().await {
+            Some(extension) => tokio::time::timeout(extension, handler).await,
+            None => Err(elapsed),
+        },
+        outcome => outcome,
+    }
+}
+
+/// Reply with an error only the invoking user sees, following up if the command had
+/// already been responded to.
+pub async fn respond_error(
+    ctx: &Context,
+    command: &CommandInteraction,
+    message: &str,
+) -> serenity::Result<()> {
+    let response = CreateInteractionResponseMessage::new()
+        .content(message)
+        .ephemeral(true);
+    if command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    followup::send(ctx, command, Kind::Error, message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::collections::BTreeMap;
+
+    fn config() -> CommandsConfig {
+        CommandsConfig {
+            disabled: vec!["play".to_string()],
+            guilds: BTreeMap::from([("111".to_string(), vec!["about".to_string()])]),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(None, "about", true)]
+    #[case(Some(222), "about", true)]
+    #[case(Some(111), "about", false)]
+    #[case(None, "play", false)]
+    #[case(Some(222), "play", false)]
+    fn test_is_enabled(#[case] guild_id: Option<u64>, #[case] name: &str, #[case] expected: bool) {
+        assert_eq!(
+            is_enabled(&config(), guild_id.map(GuildId::new), name),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(Some(111), "pause", false)]
+    #[case(Some(111), "playlist", true)]
+    #[case(Some(222), "pause", true)]
+    #[case(Some(222), "history", false)]
+    #[case(Some(222), "playlist", false)]
+    #[case(None, "admin", false)]
+    fn test_is_enabled_by_category(
+        #[case] guild_id: Option<u64>,
+        #[case] name: &str,
+        #[case] expected: bool,
+    ) {
+        let config = CommandsConfig {
+            disabled: vec!["admin".to_string()],
+            guilds: BTreeMap::from([
+                ("111".to_string(), vec!["playback".to_string()]),
+                ("222".to_string(), vec!["playlists".to_string()]),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            is_enabled(&config, guild_id.map(GuildId::new), name),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_guild_commands_leave_out_disabled() {
+        let names = |commands: Vec<(&'static str, CreateCommand)>| -> Vec<&'static str> {
+            commands.into_iter().map(|(name, _)| name).collect()
+        };
+        let config = config();
+
+        let global = names(global(&config));
+        assert!(!global.contains(&about::NAME));
+        assert!(!global.contains(&play::NAME));
+        assert_eq!(global.len(), definitions().len() - 2);
+        assert_eq!(
+            names(for_guild(&config, GuildId::new(111))),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            names(for_guild(&config, GuildId::new(222))),
+            vec![about::NAME]
+        );
+        assert!(for_guild(&CommandsConfig::default(), GuildId::new(111)).is_empty());
+    }
+
+    #[test]
+    fn test_categories_name_real_commands() {
+        let names: Vec<&str> = definitions().into_iter().map(|(name, _)| name).collect();
+        for (category, members) in CATEGORIES {
+            for member in members {
+                assert!(names.contains(member), "{category}: {member}");
+            }
+        }
+        for name in &names {
+            assert!(
+                CATEGORIES.iter().any(|(_, members)| members.contains(name)),
+                "{name} has no category"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_guard_catches_panics() {
+        let outcome = guard(async { panic!("boom") }, Duration::ZERO, async || None).await;
+        assert!(matches!(outcome, Ok(Err(_))));
+        let outcome = guard(async { 1 }, Duration::from_secs(1), async || None).await;
+        assert!(matches!(outcome, Ok(Ok(1))));
+    }
+
+    #[tokio::test]
+    async fn test_guard_extends() {
+        let slow = tokio::time::sleep(Duration::from_millis(50));
+        let outcome = guard(slow, Duration::from_millis(1), async || {
+            Some(Duration::from_secs(5))
+        })
+        .await;
+        assert!(matches!(outcome, Ok(Ok(()))));
+    }
+
+    /// A handler cancelled by its timeout takes its yt-dlp lookup down with it.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_timed_out_handler_kills_ytdlp() {
+        use crate::config::YtdlpConfig;
+        use crate::player::ytdlp::Ytdlp;
+        use songbird::input::Compose;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join("triboferrin_guard_kills_ytdlp");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let pid_file = dir.join("pid");
+        let program = dir.join("yt-dlp");
+        std::fs::write(
+            &program,
+            format!(
+                "#!/bin/sh\necho $$ > {}\nexec sleep 30\n",
+                pid_file.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let ytdlp = Ytdlp::new(&YtdlpConfig {
+            path: program.display().to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let handler = async {
+            let _ = ytdlp
+                .source("https://example.com/slow")
+                .aux_metadata()
+                .await;
+        };
+        let outcome = guard(handler, Duration::from_millis(500), async || None).await;
+        assert!(outcome.is_err());
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = format!("/proc/{}/stat", pid.trim());
+        let running = || {
+            std::fs::read_to_string(&stat).is_ok_and(|stat| {
+                // The state follows the parenthesised command name; zombies are gone
+                !stat
+                    .rsplit_once(") ")
+                    .is_some_and(|(_, rest)| rest.starts_with('Z'))
+            })
+        };
+        for _ in 0..50 {
+            if !running() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!running(), "yt-dlp {} still running", pid.trim());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_all_skips_disabled() {
+        assert_eq!(all(&CommandsConfig::default()).len(), definitions().len());
+
+        let config = CommandsConfig {
+            disabled: vec![about::NAME.to_string()],
+            ..Default::default()
+        };
+        assert_eq!(all(&config).len(), definitions().len() - 1);
+    }
+}
@@ -0,0 +1,202 @@
+use serenity::all::{
+    ChannelId, CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    GuildId,
+};
+
+use std::sync::Arc;
+
+use crate::cleanup::Kind;
+use crate::commands::{controls, followup, respond_error};
+use crate::config::ThemeConfig;
+use crate::player::queue::QueuedTrack;
+use crate::player::{self, Outcome, Player};
+use crate::views::Card;
+
+pub const NAME: &str = "play";
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new(NAME)
+        .description("Play audio from a YouTube or Spotify URL in your voice channel")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "url",
+                "YouTube or Spotify URL to play",
+            )
+            .required(true),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "radio",
+            "Play the link as a direct audio stream, such as an internet radio station",
+        ))
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_error(ctx, command, "Music can only be played in a server.").await;
+    };
+    let url = command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "url")
+        .and_then(|option| option.value.as_str())
+        .unwrap_or_default()
+        .trim();
+    let radio = command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "radio")
+        .and_then(|option| option.value.as_bool())
+        .unwrap_or(false);
+    if !is_url(url) {
+        return respond_error(ctx, command, "Please give a link starting with https://").await;
+    }
+    let Some(channel_id) = player::voice_channel(ctx, guild_id, command.user.id) else {
+        return respond_error(ctx, command, "Join a voice channel first.").await;
+    };
+
+    // Loading the track runs yt-dlp, which easily exceeds the 3 second response window
+    command.defer(&ctx.http).await?;
+
+    let outcome = if radio {
+        let tracks = player.resolve_stream(url, command.user.id).await;
+        enqueue_tracks(ctx, command, player, channel_id, tracks).await
+    } else {
+        enqueue(ctx, command, player, channel_id, url).await
+    };
+    followup::reply_card(ctx, command, Kind::NowPlaying, outcome, theme).await
+}
+
+/// Look `url` up and play or queue it in the command's guild, returning the card to show
+/// the user.
+pub async fn enqueue(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    channel_id: ChannelId,
+    url: &str,
+) -> Result<Card, String> {
+    let tracks = player.resolve(url, command.user.id).await;
+    enqueue_tracks(ctx, command, player, channel_id, tracks).await
+}
+
+/// Play or queue looked up `tracks` in the command's guild, returning the card to show the
+/// user.
+pub async fn enqueue_tracks(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    channel_id: ChannelId,
+    tracks: Result<Vec<QueuedTrack>, String>,
+) -> Result<Card, String> {
+    let guild_id = command
+        .guild_id
+        .ok_or("Music can only be played in a server.")?;
+
+    let mut more = 0;
+    let outcome = match tracks {
+        Ok(tracks) => {
+            more = tracks.len().saturating_sub(1);
+            player.play(ctx, guild_id, channel_id, tracks).await
+        }
+        Err(e) => Err(e),
+    };
+    let card = match outcome {
+        Ok(outcome) => describe(player, guild_id, channel_id, outcome),
+        Err(e) => {
+            tracing::warn!("/{} in guild {} failed: {}", command.data.name, guild_id, e);
+            return Err(e);
+        }
+    };
+    Ok(match more {
+        0 => card,
+        1 => card.field("Also queued", "1 more track", true),
+        _ => card.field("Also queued", format!("{more} more tracks"), true),
+    })
+}
+
+/// The now-playing card for a track played or queued in `channel_id`, with the loop mode
+/// when looping.
+pub fn describe(
+    player: &Player,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    outcome: Outcome,
+) -> Card {
+    let card = match outcome {
+        Outcome::Playing(track) => Card::new("Now playing")
+            .field("Track", track.title, false)
+            .field("Channel", format!("<#{channel_id}>"), true),
+        Outcome::Queued(track, position) => {
+            let card = Card::new("Queued")
+                .field("Track", track.title, false)
+                .field("Position", position.to_string(), true);
+            match player.now_playing(guild_id) {
+                Some(current) => card.field("Now playing", current.title, false),
+                None => card,
+            }
+        }
+    };
+    controls::with_loop(card, player, guild_id)
+}
+
+/// Whether `value` looks like a link rather than search terms.
+pub fn is_url(value: &str) -> bool {
+    value.starts_with("https://") || value.starts_with("http://")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PlayerConfig, YtdlpConfig};
+    use crate::player::LoopMode;
+    use crate::player::queue::Source;
+    use crate::player::ytdlp::Ytdlp;
+    use rstest::rstest;
+    use serenity::all::UserId;
+
+    #[rstest]
+    #[case("https://www.youtube.com/watch?v=abc", true)]
+    #[case("http://radio.example.com/stream", true)]
+    #[case("daft punk", false)]
+    #[case("youtube.com/watch?v=abc", false)]
+    fn test_is_url(#[case] value: &str, #[case] expected: bool) {
+        assert_eq!(is_url(value), expected);
+    }
+
+    #[tokio::test]
+    async fn test_describe_mentions_loop_mode() {
+        let player = Player::new(
+            Ytdlp::new(&YtdlpConfig::default()).unwrap(),
+            None,
+            PlayerConfig::default(),
+        );
+        let guild_id = GuildId::new(1);
+        let track = QueuedTrack {
+            url: "https://example.com/song".to_string(),
+            title: "Song".to_string(),
+            duration: None,
+            requester: UserId::new(2),
+            source: Source::Ytdlp,
+        };
+        let playing = || Outcome::Playing(track.clone());
+
+        assert_eq!(
+            describe(&player, guild_id, ChannelId::new(3), playing()).text(),
+            "Now playing\nTrack: Song\nChannel: <#3>"
+        );
+        player.set_loop_mode(guild_id, LoopMode::Track);
+        assert_eq!(
+            describe(&player, guild_id, ChannelId::new(3), playing()).text(),
+            "Now playing\nTrack: Song\nChannel: <#3>\nLoop: track"
+        );
+    }
+}
@@ -0,0 +1,467 @@
+use serenity::all::{
+    AutocompleteChoice, CommandInteraction, CommandOptionType, Context, CreateAutocompleteResponse,
+    CreateCommand, CreateCommandOption, CreateInteractionResponse, GuildId, ResolvedOption,
+    ResolvedValue,
+};
+use std::sync::Arc;
+
+use crate::cleanup::Kind;
+use crate::commands::{followup, play, respond_error};
+use crate::config::ThemeConfig;
+use crate::player::library::{self, Library};
+use crate::player::queue::QueuedTrack;
+use crate::player::reference::{self, Match};
+use crate::player::{self, Player};
+use crate::storage::{Playlist, PlaylistSummary, Storage};
+use crate::views::Card;
+
+pub const NAME: &str = "playlist";
+const CREATE: &str = "create";
+const ADD: &str = "add";
+const REMOVE: &str = "remove";
+const PLAY: &str = "play";
+const LIST: &str = "list";
+/// Most tracks a saved playlist holds.
+const TRACK_LIMIT: usize = 200;
+const NAME_MAX_LEN: usize = 50;
+/// Lines shown when listing playlists or tracks.
+const LIST_LIMIT: usize = 25;
+const AUTOCOMPLETE_RESULTS: usize = 25;
+const NOT_CONFIGURED: &str = "Playlists need a database, and none is configured.";
+
+pub fn register() -> CreateCommand {
+    let name = || {
+        CreateCommandOption::new(CommandOptionType::String, "name", "Playlist name")
+            .required(true)
+            .set_autocomplete(true)
+    };
+    CreateCommand::new(NAME)
+        .description("Save and play named playlists")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                CREATE,
+                "Save the current track and queue as a new playlist",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "name", "Playlist name")
+                    .required(true)
+                    .max_length(NAME_MAX_LEN as u16),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                ADD,
+                "Add a link, or the current track, to a playlist",
+            )
+            .add_sub_option(name())
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "url",
+                "Link to add instead of the current track",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                REMOVE,
+                "Remove a track from a playlist",
+            )
+            .add_sub_option(name())
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "track",
+                    "Position, \"last\" or part of the title",
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                PLAY,
+                "Queue a playlist in your voice channel",
+            )
+            .add_sub_option(name()),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                LIST,
+                "List this server's playlists, or the tracks of one",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "name", "Playlist name")
+                    .set_autocomplete(true),
+            ),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    storage: Option<&Storage>,
+    library: Option<&Library>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_error(ctx, command, "Playlists only exist in servers.").await;
+    };
+    let Some(storage) = storage else {
+        return respond_error(ctx, command, NOT_CONFIGURED).await;
+    };
+
+    let options = command.data.options();
+    let Some(ResolvedOption {
+        name: subcommand,
+        value: ResolvedValue::SubCommand(options),
+        ..
+    }) = options.first()
+    else {
+        return respond_error(ctx, command, "Pick a playlist subcommand.").await;
+    };
+    let text = |name: &str| {
+        options
+            .iter()
+            .find(|option| option.name == name)
+            .and_then(|option| match option.value {
+                ResolvedValue::String(value) => Some(value.trim()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    };
+    let name = text("name");
+
+    let card = match *subcommand {
+        CREATE => {
+            if name.is_empty() || name.chars().count() > NAME_MAX_LEN {
+                let message =
+                    format!("Give the playlist a name of up to {NAME_MAX_LEN} characters.");
+                return respond_error(ctx, command, &message).await;
+            }
+            let tracks: Vec<QueuedTrack> = player
+                .now_playing(guild_id)
+                .into_iter()
+                .chain(player.queues.list(guild_id))
+                .take(TRACK_LIMIT)
+                .collect();
+            match storage
+                .create_playlist(guild_id, name, command.user.id, &tracks)
+                .await
+            {
+                Ok(()) => Card::new("Playlist saved")
+                    .field("Playlist", name, true)
+                    .field("Tracks", tracks.len().to_string(), true),
+                Err(e) => return respond_error(ctx, command, &e).await,
+            }
+        }
+        ADD => {
+            let playlist = match editable(command, storage, name).await {
+                Ok(playlist) => playlist,
+                Err(e) => return respond_error(ctx, command, &e).await,
+            };
+            let url = text("url");
+            command.defer(&ctx.http).await?;
+            let outcome = add(command, player, storage, &playlist, url).await;
+            return followup::reply(ctx, command, Kind::Reply, outcome).await;
+        }
+        REMOVE => {
+            let removed = match editable(command, storage, name).await {
+                Ok(playlist) => remove(storage, guild_id, &playlist, text("track")).await,
+                Err(e) => Err(e),
+            };
+            match removed {
+                Ok((position, track)) => Card::new("Removed from playlist")
+                    .field("Track", track.title, false)
+                    .field("Playlist", name, true)
+                    .field("Position", position.to_string(), true),
+                Err(e) => return respond_error(ctx, command, &e).await,
+            }
+        }
+        PLAY => {
+            let tracks = match storage.playlist(guild_id, name).await {
+                Ok(playlist) if playlist.tracks.is_empty() => {
+                    let message = format!("\"{}\" has no tracks yet.", playlist.name);
+                    return respond_error(ctx, command, &message).await;
+                }
+                Ok(playlist) => playlist.tracks,
+                Err(e) => return respond_error(ctx, command, &e).await,
+            };
+            let Some(channel_id) = player::voice_channel(ctx, guild_id, command.user.id) else {
+                return respond_error(ctx, command, "Join a voice channel first.").await;
+            };
+
+            command.defer(&ctx.http).await?;
+            let (tracks, left_out) = library::playable(library, tracks).await;
+            let tracks: Vec<QueuedTrack> = tracks
+                .into_iter()
+                .map(|track| QueuedTrack {
+                    requester: command.user.id,
+                    ..track
+                })
+                .collect();
+            let outcome = if tracks.is_empty() {
+                Err(format!(
+                    "None of the tracks in \"{name}\" are in the music library anymore."
+                ))
+            } else {
+                play::enqueue_tracks(ctx, command, player, channel_id, Ok(tracks)).await
+            };
+            let outcome = outcome.map(|card| match left_out {
+                0 => card,
+                1 => card.field("Left out", "1 file no longer in the music library", true),
+                _ => card.field(
+                    "Left out",
+                    format!("{left_out} files no longer in the music library"),
+                    true,
+                ),
+            });
+            return followup::reply_card(ctx, command, Kind::NowPlaying, outcome, theme).await;
+        }
+        _ if name.is_empty() => match storage.playlists(guild_id).await {
+            Ok(playlists) => Card::new("Playlists").description(describe_playlists(&playlists)),
+            Err(e) => return respond_error(ctx, command, &e).await,
+        },
+        _ => match storage.playlist(guild_id, name).await {
+            Ok(playlist) => Card::new(format!("Playlist: {}", playlist.name))
+                .description(describe_tracks(&playlist.tracks))
+                .field("Tracks", playlist.tracks.len().to_string(), true)
+                .field("Created by", format!("<@{}>", playlist.owner), true),
+            Err(e) => return respond_error(ctx, command, &e).await,
+        },
+    };
+
+    let response = card.message(theme, Some(guild_id));
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await
+}
+
+/// Suggest the guild's playlists matching what has been typed.
+pub async fn autocomplete(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    storage: Option<&Storage>,
+) -> serenity::Result<()> {
+    let typed = interaction
+        .data
+        .autocomplete()
+        .map(|option| option.value.trim().to_lowercase())
+        .unwrap_or_default();
+
+    let mut choices = Vec::new();
+    if let (Some(storage), Some(guild_id)) = (storage, interaction.guild_id) {
+        choices = storage
+            .playlists(guild_id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|playlist| playlist.name.to_lowercase().contains(&typed))
+            .take(AUTOCOMPLETE_RESULTS)
+            .map(|playlist| AutocompleteChoice::new(playlist.name.clone(), playlist.name))
+            .collect();
+    }
+
+    let response = CreateAutocompleteResponse::new().set_choices(choices);
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+        .await
+}
+
+/// A playlist the user may change: their own, or any when they can manage the server.
+async fn editable(
+    command: &CommandInteraction,
+    storage: &Storage,
+    name: &str,
+) -> Result<Playlist, String> {
+    let guild_id = command.guild_id.ok_or("Playlists only exist in servers.")?;
+    let playlist = storage.playlist(guild_id, name).await?;
+    let manages_server = command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild());
+    if playlist.owner != command.user.id && !manages_server {
+        return Err(format!(
+            "Only <@{}> or someone who can manage the server can change \"{}\".",
+            playlist.owner, playlist.name
+        ));
+    }
+    Ok(playlist)
+}
+
+/// Add `url`, or the current track when it is empty, and describe what happened.
+async fn add(
+    command: &CommandInteraction,
+    player: &Player,
+    storage: &Storage,
+    playlist: &Playlist,
+    url: &str,
+) -> Result<String, String> {
+    let guild_id = command.guild_id.ok_or("Playlists only exist in servers.")?;
+    let tracks = if url.is_empty() {
+        let track = player
+            .now_playing(guild_id)
+            .ok_or("Nothing is playing. Give a link to add instead.")?;
+        vec![track]
+    } else {
+        check_link(url)?;
+        player.resolve(url, command.user.id).await?
+    };
+
+    let (added, total) = storage
+        .add_tracks(guild_id, &playlist.name, &tracks, TRACK_LIMIT)
+        .await?;
+    let tracks = &tracks[..added];
+    Ok(match tracks {
+        [track] => format!(
+            "Added **{}** to \"{}\" ({total} tracks).",
+            track.title, playlist.name
+        ),
+        _ => format!(
+            "Added {} tracks to \"{}\" ({total} tracks).",
+            tracks.len(),
+            playlist.name
+        ),
+    })
+}
+
+/// Only links are saved, as `/play` only resolves links; other text could reach yt-dlp
+/// and would be replayed on every `/playlist play`.
+fn check_link(url: &str) -> Result<(), String> {
+    if play::is_url(url) {
+        Ok(())
+    } else {
+        Err("Give a link starting with https:// or http://.".to_string())
+    }
+}
+
+/// Remove the track `text` refers to, returning its 1-based position and the track.
+async fn remove(
+    storage: &Storage,
+    guild_id: GuildId,
+    playlist: &Playlist,
+    text: &str,
+) -> Result<(usize, QueuedTrack), String> {
+    let index = match reference::find(text, &playlist.tracks) {
+        Match::One(index) => index,
+        Match::Ambiguous(indexes) => {
+            let mut message = format!("Several tracks in \"{}\" match \"{text}\":", playlist.name);
+            for index in indexes {
+                message.push_str(&format!(
+                    "\n{}. {}",
+                    index + 1,
+                    playlist.tracks[index].title
+                ));
+            }
+            message.push_str("\nRun the command again with the number of the one you mean.");
+            return Err(message);
+        }
+        Match::None => {
+            return Err(format!(
+                "Nothing in \"{}\" matches \"{text}\".",
+                playlist.name
+            ));
+        }
+    };
+
+    storage
+        .remove_track(guild_id, &playlist.name, index)
+        .await?;
+    Ok((index + 1, playlist.tracks[index].clone()))
+}
+
+fn describe_playlists(playlists: &[PlaylistSummary]) -> String {
+    if playlists.is_empty() {
+        return format!("No playlists yet. Save one with /{NAME} {CREATE}.");
+    }
+    let mut lines: Vec<String> = playlists
+        .iter()
+        .take(LIST_LIMIT)
+        .map(|playlist| match playlist.tracks {
+            1 => format!("**{}** - 1 track", playlist.name),
+            tracks => format!("**{}** - {tracks} tracks", playlist.name),
+        })
+        .collect();
+    if playlists.len() > LIST_LIMIT {
+        lines.push(format!("… and {} more", playlists.len() - LIST_LIMIT));
+    }
+    lines.join("\n")
+}
+
+fn describe_tracks(tracks: &[QueuedTrack]) -> String {
+    if tracks.is_empty() {
+        return "This playlist has no tracks yet.".to_string();
+    }
+    let mut lines: Vec<String> = tracks
+        .iter()
+        .take(LIST_LIMIT)
+        .enumerate()
+        .map(|(index, track)| format!("{}. {}", index + 1, track.title))
+        .collect();
+    if tracks.len() > LIST_LIMIT {
+        lines.push(format!("… and {} more", tracks.len() - LIST_LIMIT));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::queue::Source;
+    use rstest::rstest;
+    use serenity::all::UserId;
+
+    #[rstest]
+    #[case("https://www.youtube.com/watch?v=abc", true)]
+    #[case("--batch-file=/etc/passwd", false)]
+    #[case("-o /tmp/x https://example.com", false)]
+    #[case("ytsearch1:song", false)]
+    fn test_check_link(#[case] url: &str, #[case] accepted: bool) {
+        assert_eq!(check_link(url).is_ok(), accepted);
+    }
+
+    fn track(title: &str) -> QueuedTrack {
+        QueuedTrack {
+            url: format!("https://example.com/{title}"),
+            title: title.to_string(),
+            duration: None,
+            requester: UserId::new(1),
+            source: Source::Ytdlp,
+        }
+    }
+
+    #[test]
+    fn test_describe_playlists() {
+        let playlists = [
+            PlaylistSummary {
+                name: "Chill".to_string(),
+                tracks: 1,
+            },
+            PlaylistSummary {
+                name: "Party".to_string(),
+                tracks: 12,
+            },
+        ];
+        assert_eq!(
+            describe_playlists(&playlists),
+            "**Chill** - 1 track\n**Party** - 12 tracks"
+        );
+        assert_eq!(
+            describe_playlists(&[]),
+            "No playlists yet. Save one with /playlist create."
+        );
+    }
+
+    #[test]
+    fn test_describe_tracks() {
+        assert_eq!(describe_tracks(&[track("a"), track("b")]), "1. a\n2. b");
+        let tracks: Vec<QueuedTrack> = (0..30).map(|n| track(&n.to_string())).collect();
+        let description = describe_tracks(&tracks);
+        assert_eq!(description.lines().count(), LIST_LIMIT + 1);
+        assert!(description.ends_with("… and 5 more"));
+    }
+}
@@ -0,0 +1,180 @@
+use serenity::all::{
+    ButtonStyle, CommandInteraction, ComponentInteraction, Context, CreateActionRow, CreateButton,
+    CreateCommand, CreateInteractionResponse, CreateInteractionResponseMessage, GuildId,
+};
+use std::sync::Arc;
+
+use crate::commands::components::{self, Components};
+use crate::commands::respond_error;
+use crate::config::ThemeConfig;
+use crate::player::Player;
+use crate::player::queue::QueuedTrack;
+use crate::views::{self, Card};
+
+pub const NAME: &str = "queue";
+
+const PAGE_SIZE: usize = 10;
+/// Prefix of the page buttons' state, followed by the page to show.
+const PAGE_PREFIX: &str = "page:";
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new(NAME).description("Show the tracks waiting to be played")
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    components: &Components,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_error(ctx, command, "Queues only exist in servers.").await;
+    };
+
+    let response = render(player, guild_id, 0, components, theme);
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+        .await
+}
+
+/// Page a queue button's state points to.
+fn page_from_state(state: &str) -> Option<usize> {
+    state.strip_prefix(PAGE_PREFIX)?.parse().ok()
+}
+
+/// Re-render the queue message at the page a Previous/Next button points to.
+pub async fn turn_page(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    state: &str,
+    player: &Arc<Player>,
+    components: &Components,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let (Some(guild_id), Some(page)) = (component.guild_id, page_from_state(state)) else {
+        return components::respond_expired(ctx, component).await;
+    };
+
+    let response = render(player, guild_id, page, components, theme);
+    component
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(response),
+        )
+        .await
+}
+
+fn render(
+    player: &Player,
+    guild_id: GuildId,
+    page: usize,
+    components: &Components,
+    theme: &ThemeConfig,
+) -> CreateInteractionResponseMessage {
+    let tracks = player.queues.list(guild_id);
+    let pages = page_count(tracks.len());
+    let page = page.min(pages - 1);
+
+    let now_playing = player
+        .now_playing(guild_id)
+        .map_or_else(|| "Nothing".to_string(), |track| describe(&track));
+    let card = Card::new(format!("Queue (page {} of {})", page + 1, pages))
+        .description(page_lines(&tracks, page))
+        .field("Now playing", now_playing, false)
+        .field("Tracks queued", tracks.len().to_string(), true)
+        .field("Loop", player.loop_mode(guild_id).name(), true);
+
+    let button = |page: usize| {
+        CreateButton::new(components.custom_id(NAME, &format!("{PAGE_PREFIX}{page}")))
+            .style(ButtonStyle::Secondary)
+    };
+    let buttons = CreateActionRow::Buttons(vec![
+        button(page.saturating_sub(1))
+            .label("Previous")
+            .disabled(page == 0),
+        button(page + 1).label("Next").disabled(page + 1 >= pages),
+    ]);
+    card.message(theme, Some(guild_id))
+        .components(vec![buttons])
+}
+
+fn page_count(tracks: usize) -> usize {
+    tracks.div_ceil(PAGE_SIZE).max(1)
+}
+
+/// Numbered lines for the tracks on one page.
+fn page_lines(tracks: &[QueuedTrack], page: usize) -> String {
+    if tracks.is_empty() {
+        return "The queue is empty.".to_string();
+    }
+
+    tracks
+        .iter()
+        .enumerate()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .map(|(index, track)| format!("{}. {}", index + 1, describe(track)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn describe(track: &QueuedTrack) -> String {
+    let duration = track
+        .duration
+        .map(|duration| format!(" ({})", views::format_duration(duration)))
+        .unwrap_or_default();
+    format!("**{}**{} - <@{}>", track.title, duration, track.requester)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::queue::Source;
+    use rstest::rstest;
+    use serenity::all::UserId;
+
+    fn tracks(count: usize) -> Vec<QueuedTrack> {
+        (1..=count)
+            .map(|n| QueuedTrack {
+                url: format!("https://example.com/{n}"),
+                title: format!("Track {n}"),
+                duration: None,
+                requester: UserId::new(7),
+                source: Source::Ytdlp,
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(0, 1)]
+    #[case(1, 1)]
+    #[case(10, 1)]
+    #[case(11, 2)]
+    #[case(25, 3)]
+    fn test_page_count(#[case] tracks: usize, #[case] expected: usize) {
+        assert_eq!(page_count(tracks), expected);
+    }
+
+    #[test]
+    fn test_page_lines() {
+        let tracks = tracks(12);
+        let first = page_lines(&tracks, 0);
+        assert_eq!(first.lines().count(), 10);
+        assert!(first.starts_with("1. **Track 1** - <@7>"));
+
+        assert_eq!(
+            page_lines(&tracks, 1),
+            "11. **Track 11** - <@7>\n12. **Track 12** - <@7>"
+        );
+        assert_eq!(page_lines(&[], 0), "The queue is empty.");
+    }
+
+    #[rstest]
+    #[case("page:3", Some(3))]
+    #[case("page:x", None)]
+    #[case("3", None)]
+    fn test_page_from_state(#[case] state: &str, #[case] expected: Option<usize>) {
+        assert_eq!(page_from_state(state), expected);
+    }
+}
@@ -0,0 +1,124 @@
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse,
+};
+use std::sync::Arc;
+
+use crate::commands::respond_error;
+use crate::config::ThemeConfig;
+use crate::player::Player;
+use crate::player::queue::QueuedTrack;
+use crate::player::reference::{self, Match};
+use crate::views::Card;
+
+pub const NAME: &str = "remove";
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new(NAME)
+        .description("Remove a track from the queue")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "track",
+                "Queue position, \"next\", \"last\" or part of the title",
+            )
+            .required(true),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_error(ctx, command, "Queues only exist in servers.").await;
+    };
+    let text = command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "track")
+        .and_then(|option| option.value.as_str())
+        .unwrap_or_default()
+        .trim();
+
+    let removed = player
+        .queues
+        .remove_with(guild_id, |tracks| pick(text, tracks));
+    match removed {
+        Ok((position, track)) => {
+            let response = Card::new("Removed")
+                .field("Track", track.title, false)
+                .field("Position", position.to_string(), true)
+                .message(theme, Some(guild_id));
+            command
+                .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+                .await
+        }
+        Err(e) => respond_error(ctx, command, &e).await,
+    }
+}
+
+/// The queue index `text` refers to. When several tracks match, the user is asked to
+/// confirm which one by running the command again with its position.
+fn pick(text: &str, tracks: &[QueuedTrack]) -> Result<usize, String> {
+    if tracks.is_empty() {
+        return Err("The queue is empty.".to_string());
+    }
+    match reference::find(text, tracks) {
+        Match::One(index) => Ok(index),
+        Match::Ambiguous(indexes) => {
+            let mut message = format!("Several queued tracks match \"{text}\":");
+            for index in indexes {
+                message.push_str(&format!("\n{}. {}", index + 1, tracks[index].title));
+            }
+            message.push_str(&format!(
+                "\nRun /{NAME} again with the number of the one you mean."
+            ));
+            Err(message)
+        }
+        Match::None => Err(format!("Nothing in the queue matches \"{text}\".")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::queue::Source;
+    use serenity::all::UserId;
+
+    fn track(title: &str) -> QueuedTrack {
+        QueuedTrack {
+            url: format!("https://example.com/{title}"),
+            title: title.to_string(),
+            duration: None,
+            requester: UserId::new(1),
+            source: Source::Ytdlp,
+        }
+    }
+
+    #[test]
+    fn test_pick_ambiguous_lists_positions() {
+        let tracks = [
+            track("Daft Punk - One More Time"),
+            track("Air - Sexy Boy"),
+            track("Daft Punk - Digital Love"),
+        ];
+        assert_eq!(
+            pick("daft punk", &tracks),
+            Err("Several queued tracks match \"daft punk\":\n\
+                 1. Daft Punk - One More Time\n\
+                 3. Daft Punk - Digital Love\n\
+                 Run /remove again with the number of the one you mean."
+                .to_string())
+        );
+        assert_eq!(pick("3", &tracks), Ok(2));
+    }
+
+    #[test]
+    fn test_pick_empty_queue() {
+        assert_eq!(pick("next", &[]), Err("The queue is empty.".to_string()));
+    }
+}
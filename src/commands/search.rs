@@ -0,0 +1,165 @@
+use serenity::all::{
+    AutocompleteChoice, CommandInteraction, CommandOptionType, Context, CreateAutocompleteResponse,
+    CreateCommand, CreateCommandOption, CreateInteractionResponse,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cleanup::Kind;
+use crate::commands::play::{enqueue, is_url};
+use crate::commands::{followup, respond_error};
+use crate::config::ThemeConfig;
+use crate::player::{self, Player, search};
+use crate::views;
+
+pub const NAME: &str = "search";
+
+/// Discord drops autocomplete responses that take longer than 3 seconds.
+const AUTOCOMPLETE_TIMEOUT: Duration = Duration::from_millis(2500);
+/// Shorter queries are too vague to be worth a yt-dlp run per keystroke.
+const AUTOCOMPLETE_MIN_QUERY: usize = 3;
+const AUTOCOMPLETE_RESULTS: usize = 5;
+/// Discord's limit for choice names and values.
+const CHOICE_MAX_LEN: usize = 100;
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new(NAME)
+        .description("Search YouTube and play the chosen result")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "query", "What to search for")
+                .required(true)
+                .set_autocomplete(true),
+        )
+}
+
+fn query(command: &CommandInteraction) -> &str {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "query")
+        .and_then(|option| option.value.as_str())
+        .unwrap_or_default()
+        .trim()
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_error(ctx, command, "Music can only be played in a server.").await;
+    };
+    let query = query(command);
+    if query.is_empty() {
+        return respond_error(ctx, command, "Tell me what to search for.").await;
+    }
+    let Some(channel_id) = player::voice_channel(ctx, guild_id, command.user.id) else {
+        return respond_error(ctx, command, "Join a voice channel first.").await;
+    };
+
+    command.defer(&ctx.http).await?;
+
+    // Picking a suggestion submits its URL; otherwise play the best match for the text
+    let url = if is_url(query) {
+        Ok(query.to_string())
+    } else {
+        search::search(player.ytdlp(), query, 1)
+            .await
+            .and_then(|results| {
+                results
+                    .into_iter()
+                    .next()
+                    .map(|result| result.url)
+                    .ok_or_else(|| format!("Nothing found for \"{query}\"."))
+            })
+    };
+
+    let outcome = match url {
+        Ok(url) => enqueue(ctx, command, player, channel_id, &url).await,
+        Err(e) => Err(e),
+    };
+    followup::reply_card(ctx, command, Kind::NowPlaying, outcome, theme).await
+}
+
+/// Suggest the top matches for what the user has typed so far.
+pub async fn autocomplete(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    player: &Player,
+) -> serenity::Result<()> {
+    let typed = interaction
+        .data
+        .autocomplete()
+        .map(|option| option.value.trim())
+        .unwrap_or_default();
+
+    let mut choices = Vec::new();
+    if typed.chars().count() >= AUTOCOMPLETE_MIN_QUERY && !is_url(typed) {
+        match tokio::time::timeout(
+            AUTOCOMPLETE_TIMEOUT,
+            search::search(player.ytdlp(), typed, AUTOCOMPLETE_RESULTS),
+        )
+        .await
+        {
+            Ok(Ok(results)) => {
+                choices = results
+                    .into_iter()
+                    .filter(|result| result.url.len() <= CHOICE_MAX_LEN)
+                    .map(|result| AutocompleteChoice::new(choice_name(&result), result.url))
+                    .collect();
+            }
+            Ok(Err(e)) => tracing::debug!("Search suggestions failed: {}", e),
+            Err(_) => tracing::debug!("Search suggestions for \"{}\" timed out", typed),
+        }
+    }
+
+    let response = CreateAutocompleteResponse::new().set_choices(choices);
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+        .await
+}
+
+/// Result title with its length, cut to fit a choice name.
+fn choice_name(result: &search::SearchResult) -> String {
+    let suffix = result
+        .duration
+        .map(|duration| format!(" ({})", views::format_duration(duration)))
+        .unwrap_or_default();
+    let room = CHOICE_MAX_LEN - suffix.chars().count();
+    let title = if result.title.chars().count() > room {
+        let cut: String = result.title.chars().take(room - 1).collect();
+        format!("{cut}…")
+    } else {
+        result.title.clone()
+    };
+    format!("{title}{suffix}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(title: &str, duration: Option<u64>) -> search::SearchResult {
+        search::SearchResult {
+            title: title.to_string(),
+            url: "https://www.youtube.com/watch?v=1".to_string(),
+            duration: duration.map(Duration::from_secs),
+        }
+    }
+
+    #[test]
+    fn test_choice_name() {
+        assert_eq!(choice_name(&result("Song", Some(185))), "Song (3:05)");
+        assert_eq!(choice_name(&result("Song", None)), "Song");
+    }
+
+    #[test]
+    fn test_choice_name_truncates_long_titles() {
+        let name = choice_name(&result(&"x".repeat(200), Some(60)));
+        assert_eq!(name.chars().count(), CHOICE_MAX_LEN);
+        assert!(name.ends_with("… (1:00)"));
+    }
+}
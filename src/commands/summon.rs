@@ -0,0 +1,87 @@
+use serenity::all::{
+    ChannelId, ChannelType, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, CreateInteractionResponse, GuildId,
+};
+use std::sync::Arc;
+
+use crate::commands::respond_error;
+use crate::config::ThemeConfig;
+use crate::player::{self, Player};
+use crate::views::Card;
+
+pub const SUMMON: &str = "summon";
+pub const MOVETO: &str = "moveto";
+
+fn channel_option(description: &str) -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::Channel, "channel", description)
+        .channel_types(vec![ChannelType::Voice, ChannelType::Stage])
+}
+
+pub fn register() -> Vec<(&'static str, CreateCommand)> {
+    vec![
+        (
+            SUMMON,
+            CreateCommand::new(SUMMON)
+                .description("Bring the bot to your voice channel, or the one given")
+                .add_option(channel_option("Voice channel to join instead of yours")),
+        ),
+        (
+            MOVETO,
+            CreateCommand::new(MOVETO)
+                .description("Move the bot to another voice channel, keeping the queue")
+                .add_option(channel_option("Voice channel to move to").required(true)),
+        ),
+    ]
+}
+
+pub async fn run(
+    ctx: &Context,
+    command: &CommandInteraction,
+    player: &Arc<Player>,
+    theme: &ThemeConfig,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_error(ctx, command, "Voice channels only exist in servers.").await;
+    };
+
+    let given = command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "channel")
+        .and_then(|option| option.value.as_channel_id());
+    let Some(channel_id) = given.or_else(|| player::voice_channel(ctx, guild_id, command.user.id))
+    else {
+        return respond_error(ctx, command, "Join a voice channel or pick one.").await;
+    };
+
+    match player.move_to(ctx, guild_id, channel_id).await {
+        Ok(()) => {
+            let title = if command.data.name == SUMMON {
+                "Summoned"
+            } else {
+                "Moved"
+            };
+            let response = card(title, player, guild_id, channel_id).message(theme, Some(guild_id));
+            command
+                .create_response(&ctx.http, CreateInteractionResponse::Message(response))
+                .await
+        }
+        Err(e) => respond_error(ctx, command, &e).await,
+    }
+}
+
+fn card(title: &str, player: &Player, guild_id: GuildId, channel_id: ChannelId) -> Card {
+    let card = Card::new(title).description(format!("Now in <#{channel_id}>"));
+    match player.now_playing(guild_id) {
+        Some(track) => card
+            .field("Now playing", track.title, false)
+            .field(
+                "Tracks queued",
+                player.queues.len(guild_id).to_string(),
+                true,
+            )
+            .field("Loop", player.loop_mode(guild_id).name(), true),
+        None => card,
+    }
+}
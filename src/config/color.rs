@@ -0,0 +1,90 @@
+//! Embed colors written as `"#5865f2"`, `"0x5865f2"` or a plain integer.
+//! Use with `#[serde(default, deserialize_with = "color::option::deserialize")]`.
+
+use serde::Deserialize;
+use serde::de::{self, Deserializer, Visitor};
+use std::fmt;
+
+struct Rgb(u32);
+
+impl<'de> Deserialize<'de> for Rgb {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(RgbVisitor)
+    }
+}
+
+struct RgbVisitor;
+
+impl Visitor<'_> for RgbVisitor {
+    type Value = Rgb;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a color such as \"#5865f2\" or an integer")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Rgb, E> {
+        u32::try_from(value)
+            .ok()
+            .filter(|value| *value <= 0xff_ffff)
+            .map(Rgb)
+            .ok_or_else(|| E::custom(format!("color out of range: {value}")))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Rgb, E> {
+        u64::try_from(value)
+            .map_err(|_| E::custom(format!("color out of range: {value}")))
+            .and_then(|value| self.visit_u64(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Rgb, E> {
+        parse(value).map(Rgb).map_err(E::custom)
+    }
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u32>, D::Error> {
+        Option::<Rgb>::deserialize(deserializer).map(|rgb| rgb.map(|rgb| rgb.0))
+    }
+}
+
+/// Parse a six digit hex color, with an optional `#` or `0x` prefix.
+fn parse(value: &str) -> Result<u32, String> {
+    let trimmed = value.trim();
+    let hex = trimmed
+        .strip_prefix('#')
+        .or_else(|| trimmed.strip_prefix("0x"))
+        .unwrap_or(trimmed);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "invalid color `{value}`, expected six hex digits such as \"#5865f2\""
+        ));
+    }
+    u32::from_str_radix(hex, 16).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("#5865f2", 0x5865f2)]
+    #[case("0x5865F2", 0x5865f2)]
+    #[case("000000", 0)]
+    fn test_parse(#[case] value: &str, #[case] expected: u32) {
+        assert_eq!(parse(value), Ok(expected));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("#fff")]
+    #[case("#gggggg")]
+    #[case("blue")]
+    fn test_parse_invalid(#[case] value: &str) {
+        assert!(parse(value).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! Durations in seconds that may also be written humanely, e.g. `"90s"`, `"5m"` or `"1h30m"`.
+//! Use with `#[serde(deserialize_with = "duration::deserialize")]`; values serialize as plain seconds.
+
+use serde::Deserialize;
+use serde::de::{self, Deserializer, Visitor};
+use std::fmt;
+
+struct Seconds(u64);
+
+impl<'de> Deserialize<'de> for Seconds {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(SecondsVisitor)
+    }
+}
+
+struct SecondsVisitor;
+
+impl Visitor<'_> for SecondsVisitor {
+    type Value = Seconds;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number of seconds or a duration such as \"5m\" or \"1h30m\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Seconds, E> {
+        Ok(Seconds(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Seconds, E> {
+        u64::try_from(value)
+            .map(Seconds)
+            .map_err(|_| E::custom(format!("duration cannot be negative: {value}")))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Seconds, E> {
+        parse(value).map(Seconds).map_err(E::custom)
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Seconds::deserialize(deserializer).map(|seconds| seconds.0)
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        Option::<Seconds>::deserialize(deserializer).map(|seconds| seconds.map(|seconds| seconds.0))
+    }
+}
+
+/// Parse a duration made of `<number><unit>` parts (units `s`, `m`, `h`, `d`, `w`) into seconds.
+/// A bare number is taken as seconds.
+fn parse(value: &str) -> Result<u64, String> {
+    let invalid =
+        || format!("invalid duration `{value}`, expected e.g. \"30s\", \"5m\" or \"1h30m\"");
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(seconds) = trimmed.parse::<u64>() {
+        return Ok(seconds);
+    }
+
+    let mut total: u64 = 0;
+    let mut rest = trimmed;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let number: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = rest[digits..].trim_start();
+
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "s" | "sec" | "secs" => 1,
+            "m" | "min" | "mins" => 60,
+            "h" | "hr" | "hrs" => 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        rest = rest[unit_len..].trim_start();
+
+        total = number
+            .checked_mul(scale)
+            .and_then(|part| total.checked_add(part))
+            .ok_or_else(|| format!("duration `{value}` is too long"))?;
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("30", 30)]
+    #[case("30s", 30)]
+    #[case("5m", 300)]
+    #[case("1h30m", 5400)]
+    #[case("1h 30m", 5400)]
+    #[case("2d", 172800)]
+    #[case("1w", 604800)]
+    #[case(" 10 min ", 600)]
+    fn test_parse(#[case] value: &str, #[case] expected: u64) {
+        assert_eq!(parse(value), Ok(expected));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("m")]
+    #[case("5x")]
+    #[case("-5m")]
+    #[case("1.5h")]
+    #[case("99999999999999999999w")]
+    fn test_parse_invalid(#[case] value: &str) {
+        assert!(parse(value).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use figment::{
+    Error, Metadata, Profile, Provider, Source,
+    providers::{Format, Toml},
+    value::{Dict, Map, Value},
+};
+use std::path::{Path, PathBuf};
+
+const INCLUDE_KEY: &str = "include";
+
+/// Configuration source with `include = [...]` files and `${ENV_VAR}` references resolved.
+///
+/// Included TOML files are resolved relative to the including file and override its
+/// values, later entries winning. `$$` produces a literal `$`.
+pub struct Expanded<P> {
+    source: P,
+}
+
+impl<P: Provider> Expanded<P> {
+    pub fn new(source: P) -> Self {
+        Self { source }
+    }
+}
+
+impl<P: Provider> Provider for Expanded<P> {
+    fn metadata(&self) -> Metadata {
+        self.source.metadata()
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let mut chain = Vec::new();
+        let base_dir = match self.metadata().source {
+            Some(Source::File(path)) => {
+                let path = path.canonicalize().unwrap_or(path);
+                let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                chain.push(path);
+                base_dir
+            }
+            _ => PathBuf::new(),
+        };
+
+        self.source
+            .data()?
+            .into_iter()
+            .map(|(profile, dict)| Ok((profile, expand(dict, &base_dir, &mut chain)?)))
+            .collect::<Result<_, String>>()
+            .map_err(Error::from)
+    }
+}
+
+/// Interpolate a dictionary's values, then merge its includes over it.
+fn expand(mut dict: Dict, base_dir: &Path, chain: &mut Vec<PathBuf>) -> Result<Dict, String> {
+    let includes = match dict.remove(INCLUDE_KEY) {
+        None => Vec::new(),
+        Some(Value::String(_, path)) => vec![path],
+        Some(Value::Array(_, paths)) => paths
+            .into_iter()
+            .map(|path| match path {
+                Value::String(_, path) => Ok(path),
+                _ => Err("`include` entries must be file paths".to_string()),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("`include` must be a file path or a list of file paths".into()),
+    };
+
+    for (key, value) in dict.iter_mut() {
+        interpolate(value, key)?;
+    }
+
+    for include in includes {
+        let include = interpolate_str(&include).map_err(|e| format!("`include`: {e}"))?;
+        let path = base_dir.join(&include);
+        let path = path
+            .canonicalize()
+            .map_err(|e| format!("cannot read included config file {}: {}", path.display(), e))?;
+
+        if chain.contains(&path) {
+            let cycle: Vec<String> = chain
+                .iter()
+                .chain(std::iter::once(&path))
+                .map(|path| path.display().to_string())
+                .collect();
+            return Err(format!("config include cycle: {}", cycle.join(" -> ")));
+        }
+
+        let included = Toml::file_exact(&path)
+            .data()
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .remove(&Profile::Default)
+            .unwrap_or_default();
+
+        let included_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        chain.push(path);
+        let included = expand(included, &included_dir, chain)?;
+        chain.pop();
+
+        merge(&mut dict, included);
+    }
+
+    Ok(dict)
+}
+
+/// Recursively merge `overrides` into `base`, replacing everything but nested tables.
+fn merge(base: &mut Dict, overrides: Dict) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Dict(_, base)), Value::Dict(_, overrides)) => merge(base, overrides),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Replace `${ENV_VAR}` references in every string within `value`.
+fn interpolate(value: &mut Value, key: &str) -> Result<(), String> {
+    match value {
+        Value::String(_, text) => {
+            *text = interpolate_str(text).map_err(|e| format!("`{key}`: {e}"))?;
+        }
+        Value::Array(_, values) => {
+            for value in values {
+                interpolate(value, key)?;
+            }
+        }
+        Value::Dict(_, dict) => {
+            for (child, value) in dict.iter_mut() {
+                interpolate(value, &format!("{key}.{child}"))?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn interpolate_str(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(index) = rest.find('$') {
+        result.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unterminated `${{` in \"{text}\""))?;
+            let name = &after[..end];
+            let value = std::env::var(name)
+                .map_err(|_| format!("environment variable {name} is not set"))?;
+            result.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use figment::Figment;
+    use rstest::rstest;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Sample {
+        token: String,
+        #[serde(default)]
+        url: Option<String>,
+    }
+
+    fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("triboferrin_expand_{name}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[rstest]
+    #[case("plain", "plain")]
+    #[case("${EXPAND_TEST_VAR}", "value")]
+    #[case("a-${EXPAND_TEST_VAR}-b", "a-value-b")]
+    #[case("$$5 and $x", "$5 and $x")]
+    #[case("$${EXPAND_TEST_VAR}", "${EXPAND_TEST_VAR}")]
+    fn test_interpolate_str(#[case] text: &str, #[case] expected: &str) {
+        temp_env::with_var("EXPAND_TEST_VAR", Some("value"), || {
+            assert_eq!(interpolate_str(text).unwrap(), expected);
+        });
+    }
+
+    #[rstest]
+    #[case(
+        "${EXPAND_TEST_UNSET}",
+        "environment variable EXPAND_TEST_UNSET is not set"
+    )]
+    #[case("${EXPAND_TEST_UNSET", "unterminated `${` in \"${EXPAND_TEST_UNSET\"")]
+    fn test_interpolate_str_errors(#[case] text: &str, #[case] expected: &str) {
+        temp_env::with_var("EXPAND_TEST_UNSET", None::<&str>, || {
+            assert_eq!(interpolate_str(text).unwrap_err(), expected);
+        });
+    }
+
+    #[test]
+    fn test_expanded_interpolates_values() {
+        temp_env::with_var("EXPAND_TEST_TOKEN", Some("secret"), || {
+            let sample: Sample = Figment::from(Expanded::new(Toml::string(
+                r#"token = "${EXPAND_TEST_TOKEN}""#,
+            )))
+            .extract()
+            .unwrap();
+
+            assert_eq!(sample.token, "secret");
+        });
+    }
+
+    #[test]
+    fn test_expanded_reports_key_of_missing_variable() {
+        temp_env::with_var("EXPAND_TEST_UNSET", None::<&str>, || {
+            let error = Figment::from(Expanded::new(Toml::string(
+                r#"token = "${EXPAND_TEST_UNSET}""#,
+            )))
+            .extract::<Sample>()
+            .unwrap_err();
+
+            assert!(
+                error
+                    .to_string()
+                    .contains("`token`: environment variable EXPAND_TEST_UNSET is not set")
+            );
+        });
+    }
+
+    #[test]
+    fn test_expanded_includes_override_including_file() {
+        let dir = temp_dir("includes");
+        write(&dir, "secrets.toml", r#"token = "secret""#);
+        write(&dir, "local.toml", r#"url = "https://local.example.com""#);
+        let main = write(
+            &dir,
+            "main.toml",
+            r#"
+include = ["secrets.toml", "local.toml"]
+token = "placeholder"
+url = "https://shared.example.com"
+"#,
+        );
+
+        let sample: Sample = Figment::from(Expanded::new(Toml::file(&main)))
+            .extract()
+            .unwrap();
+
+        assert_eq!(
+            sample,
+            Sample {
+                token: "secret".to_string(),
+                url: Some("https://local.example.com".to_string()),
+            }
+        );
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_expanded_nested_includes_relative_to_file() {
+        let dir = temp_dir("nested");
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        write(&dir, "conf.d/token.toml", r#"token = "nested""#);
+        write(&dir, "conf.d/all.toml", r#"include = "token.toml""#);
+        let main = write(&dir, "main.toml", r#"include = "conf.d/all.toml""#);
+
+        let sample: Sample = Figment::from(Expanded::new(Toml::file(&main)))
+            .extract()
+            .unwrap();
+
+        assert_eq!(sample.token, "nested");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_expanded_detects_include_cycle() {
+        let dir = temp_dir("cycle");
+        write(&dir, "a.toml", r#"include = "b.toml""#);
+        write(&dir, "b.toml", r#"include = "a.toml""#);
+        let main = dir.join("a.toml");
+
+        let error = Figment::from(Expanded::new(Toml::file(&main)))
+            .extract::<Sample>()
+            .unwrap_err();
+
+        assert!(error.to_string().contains("config include cycle"));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_expanded_missing_include() {
+        let dir = temp_dir("missing");
+        let main = write(&dir, "main.toml", r#"include = "missing.toml""#);
+
+        let error = Figment::from(Expanded::new(Toml::file(&main)))
+            .extract::<Sample>()
+            .unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .contains("cannot read included config file")
+        );
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_expanded_invalid_include_value() {
+        let error = Figment::from(Expanded::new(Toml::string("include = 5")))
+            .extract::<Sample>()
+            .unwrap_err();
+
+        assert!(
+            error
+                .to_string()
+                .contains("`include` must be a file path or a list of file paths")
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use figment::{
+    Figment, Profile, Provider,
+    value::{Dict, Value},
+};
+
+/// Minimum similarity for a known key to be suggested in place of an unknown one.
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// Reject keys in `figment` that do not appear in `known`.
+///
+/// Unknown top-level tables are treated as profile sections and checked against
+/// the top-level keys instead. Every unknown key is reported with its source.
+pub fn check_unknown_keys(figment: &Figment, known: &Dict) -> Result<(), String> {
+    let data = figment
+        .data()
+        .map_err(|e| e.to_string())?
+        .remove(&Profile::Default)
+        .unwrap_or_default();
+
+    let mut unknown = Vec::new();
+    for (key, value) in &data {
+        match (known.get(key), value) {
+            (Some(known_value), value) => collect_unknown(key, value, known_value, &mut unknown),
+            (None, Value::Dict(_, section)) => {
+                for (child, value) in section {
+                    let path = format!("{key}.{child}");
+                    match known.get(child) {
+                        Some(known_value) => {
+                            collect_unknown(&path, value, known_value, &mut unknown)
+                        }
+                        None => unknown.push((path, suggest(child, known))),
+                    }
+                }
+            }
+            (None, _) => unknown.push((key.clone(), suggest(key, known))),
+        }
+    }
+
+    if unknown.is_empty() {
+        return Ok(());
+    }
+
+    let messages: Vec<String> = unknown
+        .into_iter()
+        .map(|(path, suggestion)| {
+            let mut message = format!("unknown configuration key `{path}`");
+            if let Some(metadata) = figment.find_metadata(&path) {
+                message.push_str(&format!(" in {}", metadata.name));
+                if let Some(source) = &metadata.source {
+                    message.push_str(&format!(" ({source})"));
+                }
+            }
+            if let Some(suggestion) = suggestion {
+                message.push_str(&format!(", did you mean `{suggestion}`?"));
+            }
+            message
+        })
+        .collect();
+
+    Err(messages.join("\n"))
+}
+
+/// Walk a known key's value, collecting unknown keys below it.
+/// Tables that are empty by default are free-form maps and accept any key.
+fn collect_unknown(
+    path: &str,
+    value: &Value,
+    known: &Value,
+    unknown: &mut Vec<(String, Option<String>)>,
+) {
+    let (Value::Dict(_, dict), Value::Dict(_, known)) = (value, known) else {
+        return;
+    };
+    if known.is_empty() {
+        return;
+    }
+
+    for (key, value) in dict {
+        let child = format!("{path}.{key}");
+        match known.get(key) {
+            Some(known_value) => collect_unknown(&child, value, known_value, unknown),
+            None => unknown.push((child, suggest(key, known))),
+        }
+    }
+}
+
+/// Find the known key most similar to `key`, if any is close enough.
+fn suggest(key: &str, known: &Dict) -> Option<String> {
+    known
+        .keys()
+        .map(|candidate| (strsim::jaro_winkler(key, candidate), candidate))
+        .filter(|(score, _)| *score >= SUGGESTION_THRESHOLD)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use figment::providers::{Format, Serialized, Toml};
+    use serde::Serialize;
+
+    #[derive(Serialize, Default)]
+    struct Nested {
+        max_messages: usize,
+    }
+
+    #[derive(Serialize, Default)]
+    struct Sample {
+        discord_token: String,
+        log_level: String,
+        cache: Nested,
+        headers: std::collections::BTreeMap<String, String>,
+    }
+
+    fn known() -> Dict {
+        Figment::from(Serialized::defaults(Sample::default()))
+            .data()
+            .unwrap()
+            .remove(&Profile::Default)
+            .unwrap()
+    }
+
+    fn check(toml: &str) -> Result<(), String> {
+        check_unknown_keys(&Figment::from(Toml::string(toml)), &known())
+    }
+
+    #[test]
+    fn test_check_unknown_keys_accepts_known_keys() {
+        assert!(
+            check(
+                r#"
+discord_token = "token"
+[cache]
+max_messages = 10
+[headers]
+X-Anything = "goes"
+[prod]
+log_level = "warn"
+[prod.cache]
+max_messages = 5
+"#
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_check_unknown_keys_suggests_typo() {
+        let error = check(r#"discord_tokne = "token""#).unwrap_err();
+        assert_eq!(
+            error,
+            "unknown configuration key `discord_tokne` in TOML source string, \
+             did you mean `discord_token`?"
+        );
+    }
+
+    #[test]
+    fn test_check_unknown_keys_nested_and_profile() {
+        let error = check(
+            r#"
+[cache]
+max_mesages = 10
+[prod]
+log_levle = "warn"
+completely_unrelated = 1
+"#,
+        )
+        .unwrap_err();
+
+        let lines: Vec<&str> = error.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("unknown configuration key `cache.max_mesages`"));
+        assert!(lines[0].ends_with("did you mean `max_messages`?"));
+        assert!(lines[1].starts_with("unknown configuration key `prod.completely_unrelated`"));
+        assert!(!lines[1].contains("did you mean"));
+        assert!(lines[2].ends_with("did you mean `log_level`?"));
+    }
+
+    #[test]
+    fn test_check_unknown_keys_scalar_under_table_key() {
+        let error = check("cache = 5\nunknown = true").unwrap_err();
+        assert!(error.starts_with("unknown configuration key `unknown`"));
+    }
+}
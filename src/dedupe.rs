@@ -0,0 +1,69 @@
+use std::collections::{HashSet, VecDeque};
+
+/// Bounded set of recently seen ids, used to drop duplicated interaction deliveries.
+/// The oldest id is forgotten once `capacity` is reached.
+#[derive(Debug)]
+pub struct RecentIds {
+    capacity: usize,
+    order: VecDeque<u64>,
+    seen: HashSet<u64>,
+}
+
+impl RecentIds {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            seen: HashSet::with_capacity(capacity),
+        }
+    }
+
+    /// Record an id, returning `false` when it was already seen.
+    pub fn insert(&mut self, id: u64) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        if !self.seen.insert(id) {
+            return false;
+        }
+
+        self.order.push_back(id);
+        if self.order.len() > self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.seen.remove(&oldest);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_rejects_duplicates() {
+        let mut ids = RecentIds::new(4);
+        assert!(ids.insert(1));
+        assert!(ids.insert(2));
+        assert!(!ids.insert(1));
+        assert!(!ids.insert(2));
+    }
+
+    #[test]
+    fn test_insert_forgets_oldest() {
+        let mut ids = RecentIds::new(2);
+        assert!(ids.insert(1));
+        assert!(ids.insert(2));
+        assert!(ids.insert(3));
+        assert!(ids.insert(1));
+        assert!(!ids.insert(3));
+    }
+
+    #[test]
+    fn test_zero_capacity_accepts_everything() {
+        let mut ids = RecentIds::new(0);
+        assert!(ids.insert(1));
+        assert!(ids.insert(1));
+    }
+}
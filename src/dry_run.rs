@@ -0,0 +1,110 @@
+use crate::config::Config;
+use crate::player::library::Library;
+use crate::player::ytdlp::Ytdlp;
+use crate::storage::Storage;
+use crate::token;
+
+/// Set up what startup would before contacting Discord, returning one line per check that
+/// passed. Needs no network: the token's shape is checked only when one is configured,
+/// and the database is created and migrated just as it is on startup.
+pub async fn check(config: &Config) -> Result<Vec<String>, String> {
+    let mut passed = Vec::new();
+    if !config.discord_token.is_empty() {
+        token::check_format(&config.discord_token)?;
+        passed.push("Discord token is well-formed (not checked with Discord)".to_string());
+    }
+
+    let version = Ytdlp::new(&config.ytdlp)?
+        .version()
+        .await
+        .map_err(|e| format!("{} ({})", e, config.ytdlp.path))?;
+    passed.push(format!("yt-dlp {} ({})", version, config.ytdlp.path));
+
+    if let Some(path) = &config.music_library_path {
+        Library::new(path)?;
+        passed.push(format!("Music library at {}", path.display()));
+    }
+    if let Some(path) = &config.database_path {
+        Storage::open(path).await?;
+        passed.push(format!("Database at {} is migrated", path.display()));
+    }
+    Ok(passed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::YtdlpConfig;
+    use std::path::PathBuf;
+
+    /// A config whose yt-dlp is a stand-in that only knows `--version`.
+    #[cfg(unix)]
+    fn config(name: &str) -> (PathBuf, Config) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("triboferrin_dry_run_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("music")).unwrap();
+        let ytdlp = dir.join("yt-dlp");
+        std::fs::write(&ytdlp, "#!/bin/sh\necho 2025.01.01\n").unwrap();
+        std::fs::set_permissions(&ytdlp, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = Config {
+            ytdlp: YtdlpConfig {
+                path: ytdlp.display().to_string(),
+                ..Default::default()
+            },
+            music_library_path: Some(dir.join("music")),
+            database_path: Some(dir.join("triboferrin.db")),
+            ..Default::default()
+        };
+        (dir, config)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_check_passes() {
+        let (dir, config) = config("passes");
+        let passed = check(&config).await.unwrap();
+        assert_eq!(passed.len(), 3);
+        assert!(passed[0].starts_with("yt-dlp 2025.01.01"));
+        assert!(dir.join("triboferrin.db").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_check_finds_misconfiguration() {
+        let (dir, config) = config("misconfiguration");
+
+        let missing_library = Config {
+            music_library_path: Some(dir.join("nowhere")),
+            ..config.clone()
+        };
+        let e = check(&missing_library).await.unwrap_err();
+        assert!(e.starts_with("music_library_path"), "{e}");
+
+        let missing_ytdlp = Config {
+            ytdlp: YtdlpConfig {
+                path: dir.join("nowhere").display().to_string(),
+                ..Default::default()
+            },
+            ..config.clone()
+        };
+        let e = check(&missing_ytdlp).await.unwrap_err();
+        assert!(e.starts_with("Could not run yt-dlp"), "{e}");
+
+        let unopenable_database = Config {
+            database_path: Some(dir.join("nowhere").join("triboferrin.db")),
+            ..config.clone()
+        };
+        let e = check(&unopenable_database).await.unwrap_err();
+        assert!(e.starts_with("database_path"), "{e}");
+
+        let malformed_token = Config {
+            discord_token: "client-secret".to_string(),
+            ..config
+        };
+        assert!(check(&malformed_token).await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+//! In-process events about playback, for subsystems that react to what the player does.
+//!
+//! The player publishes and never waits: a consumer calls [`Bus::subscribe`] and gets
+//! every event sent after that, in order. A consumer that falls too far behind skips the
+//! events it missed rather than holding up playback.
+
+use serenity::all::{ChannelId, GuildId};
+use tokio::sync::broadcast;
+
+use crate::player::queue::QueuedTrack;
+
+/// Events a slow consumer can fall behind by before it starts missing some.
+const CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The bot joined or moved to a voice channel.
+    VoiceConnected {
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    },
+    /// A track started playing.
+    TrackStarted {
+        guild_id: GuildId,
+        track: QueuedTrack,
+    },
+    /// Playback ended because the queue ran out or `/stop` was used.
+    PlaybackStopped { guild_id: GuildId },
+    /// Tracks were added to or removed from a guild's queue, which now holds `length`.
+    QueueChanged { guild_id: GuildId, length: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct Bus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl Bus {
+    pub fn publish(&self, event: Event) {
+        tracing::trace!("Event {:?}", event);
+        // Only fails when nobody is subscribed, which is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribers_get_events_in_order() {
+        let bus = Bus::default();
+        bus.publish(Event::PlaybackStopped {
+            guild_id: GuildId::new(1),
+        });
+
+        let mut events = bus.subscribe();
+        let published = [
+            Event::QueueChanged {
+                guild_id: GuildId::new(1),
+                length: 2,
+            },
+            Event::PlaybackStopped {
+                guild_id: GuildId::new(2),
+            },
+        ];
+        for event in published.clone() {
+            bus.publish(event);
+        }
+        for event in published {
+            assert_eq!(events.recv().await.unwrap(), event);
+        }
+        assert!(events.try_recv().is_err());
+    }
+}
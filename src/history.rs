@@ -0,0 +1,31 @@
+//! Records every track that starts playing, for `/history`.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::Event;
+use crate::storage::Storage;
+
+/// Write each started track to the database as it starts.
+pub fn spawn(storage: Storage, mut events: Receiver<Event>) {
+    tokio::spawn(async move {
+        loop {
+            let (guild_id, track) = match events.recv().await {
+                Ok(Event::TrackStarted { guild_id, track }) => (guild_id, track),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!("Play history missed {} playback events", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs() as i64);
+            if let Err(e) = storage.record_play(guild_id, &track, now).await {
+                tracing::warn!("Could not record \"{}\" in history: {}", track.title, e);
+            }
+        }
+    });
+}
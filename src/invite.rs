@@ -0,0 +1,64 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use serenity::all::Permissions;
+
+use crate::audit::{TEXT_PERMISSIONS, VOICE_PERMISSIONS};
+
+/// Permissions requested when inviting the bot.
+pub const INVITE_PERMISSIONS: Permissions = VOICE_PERMISSIONS.union(TEXT_PERMISSIONS);
+
+/// Extract the application id encoded in the first part of a bot token.
+pub fn application_id_from_token(token: &str) -> Option<u64> {
+    let token = token.strip_prefix("Bot ").unwrap_or(token).trim();
+    let encoded = token.split('.').next()?.trim_end_matches('=');
+    let decoded = STANDARD_NO_PAD.decode(encoded).ok()?;
+    String::from_utf8(decoded).ok()?.parse().ok()
+}
+
+/// OAuth2 URL that adds the bot to a server with the permissions it needs.
+pub fn invite_url(application_id: u64) -> String {
+    format!(
+        "https://discord.com/oauth2/authorize?client_id={}&scope=bot%20applications.commands&permissions={}",
+        application_id,
+        INVITE_PERMISSIONS.bits()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("MTIzNDU2Nzg5MDEyMzQ1Njc4.GaBcDe.signature", Some(123456789012345678))]
+    #[case(
+        "Bot MTIzNDU2Nzg5MDEyMzQ1Njc4.GaBcDe.signature",
+        Some(123456789012345678)
+    )]
+    #[case(
+        "MTIzNDU2Nzg5MDEyMzQ1Njc4OQ==.GaBcDe.signature",
+        Some(1234567890123456789)
+    )]
+    #[case("not-base64!.GaBcDe.signature", None)]
+    #[case("aGVsbG8.GaBcDe.signature", None)]
+    #[case("", None)]
+    fn test_application_id_from_token(#[case] token: &str, #[case] expected: Option<u64>) {
+        assert_eq!(application_id_from_token(token), expected);
+    }
+
+    #[test]
+    fn test_invite_url() {
+        assert_eq!(
+            invite_url(42),
+            format!(
+                "https://discord.com/oauth2/authorize?client_id=42&scope=bot%20applications.commands&permissions={}",
+                (Permissions::VIEW_CHANNEL
+                    | Permissions::CONNECT
+                    | Permissions::SPEAK
+                    | Permissions::SEND_MESSAGES
+                    | Permissions::EMBED_LINKS)
+                    .bits()
+            )
+        );
+    }
+}
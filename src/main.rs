@@ -1,20 +1,340 @@
+mod access;
+mod announce;
+mod audit;
+mod cache;
+mod cleanup;
+mod commands;
 mod config;
+mod dedupe;
+mod dry_run;
+mod events;
+mod history;
+mod invite;
+mod panic;
+mod player;
+mod presence;
+mod proxy;
+mod service;
+mod shutdown;
+mod storage;
+mod token;
+mod update;
+mod views;
 
 use clap::Parser;
-use serenity::all::GatewayIntents;
+use serenity::all::{
+    ChannelId, ComponentInteraction, GatewayError, GatewayIntents, Guild, GuildId, Interaction,
+    VoiceState,
+};
 use serenity::client::ClientBuilder;
-use serenity::http::HttpBuilder;
 use serenity::prelude::*;
 use songbird::SerenityInit;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::config::{Args, build_config};
+use crate::commands::components::Route;
+use crate::config::{
+    AboutConfig, Args, Command, CommandsConfig, GuildAccessConfig, ServiceCommand, ThemeConfig,
+    build_config,
+};
 
-struct Handler;
+/// Interaction ids remembered to drop duplicate deliveries.
+const RECENT_INTERACTIONS: usize = 1024;
+
+struct Handler {
+    started: Instant,
+    recent_interactions: std::sync::Mutex<dedupe::RecentIds>,
+    /// Guilds whose own slash commands were registered since the bot started
+    registered_guilds: std::sync::Mutex<HashSet<GuildId>>,
+    /// Gateway intents the client asked for
+    intents: GatewayIntents,
+    guilds: GuildAccessConfig,
+    about: AboutConfig,
+    commands: CommandsConfig,
+    theme: ThemeConfig,
+    player: Arc<player::Player>,
+    components: commands::components::Components,
+    library: Option<player::library::Library>,
+    storage: Option<storage::Storage>,
+}
+
+impl Handler {
+    /// Handle a button press on one of the bot's messages.
+    async fn component(&self, ctx: &Context, component: &ComponentInteraction) {
+        let custom_id = &component.data.custom_id;
+        let route = self.components.route(custom_id);
+        if let Some(Route { namespace, .. }) = route
+            && !commands::is_enabled(&self.commands, component.guild_id, namespace)
+        {
+            if let Err(e) = commands::components::respond_disabled(ctx, component).await {
+                tracing::warn!("Could not refuse button of disabled /{}: {}", namespace, e);
+            }
+            return;
+        }
+
+        let result = match route {
+            Some(Route {
+                namespace: commands::queue::NAME,
+                state,
+            }) => {
+                commands::queue::turn_page(
+                    ctx,
+                    component,
+                    state,
+                    &self.player,
+                    &self.components,
+                    &self.theme,
+                )
+                .await
+            }
+            Some(Route {
+                namespace: commands::history::NAME,
+                state,
+            }) => {
+                commands::history::requeue(
+                    ctx,
+                    component,
+                    state,
+                    &self.player,
+                    self.storage.as_ref(),
+                    self.library.as_ref(),
+                    &self.theme,
+                )
+                .await
+            }
+            _ => {
+                tracing::warn!("Received unknown or unsigned component {}", custom_id);
+                commands::components::respond_expired(ctx, component).await
+            }
+        };
+
+        if let Err(e) = result {
+            tracing::error!("Component {} failed: {}", custom_id, e);
+        }
+    }
+}
 
 #[serenity::async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _: Context, ready: serenity::model::gateway::Ready) {
+    async fn ready(&self, ctx: Context, ready: serenity::model::gateway::Ready) {
         tracing::info!("Connected as {}", ready.user.name);
+        for warning in audit::audit_intents(self.intents, ready.application.flags) {
+            tracing::warn!("{}", warning);
+        }
+
+        if let Err(e) = commands::register(&ctx, &self.commands).await {
+            tracing::error!("Failed to register slash commands: {}", e);
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let id = interaction.id();
+        let is_new = self
+            .recent_interactions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(id.get());
+        if !is_new {
+            tracing::debug!("Ignoring duplicate delivery of interaction {}", id);
+            return;
+        }
+
+        let command = match interaction {
+            Interaction::Command(command) => command,
+            Interaction::Autocomplete(interaction) => {
+                let name = interaction.data.name.as_str();
+                let result = if !commands::is_enabled(&self.commands, interaction.guild_id, name) {
+                    commands::autocomplete_disabled(&ctx, &interaction).await
+                } else {
+                    match name {
+                        commands::search::NAME => {
+                            commands::search::autocomplete(&ctx, &interaction, &self.player).await
+                        }
+                        commands::library::NAME => {
+                            commands::library::autocomplete(
+                                &ctx,
+                                &interaction,
+                                self.library.as_ref(),
+                            )
+                            .await
+                        }
+                        commands::playlist::NAME => {
+                            commands::playlist::autocomplete(
+                                &ctx,
+                                &interaction,
+                                self.storage.as_ref(),
+                            )
+                            .await
+                        }
+                        _ => return,
+                    }
+                };
+                if let Err(e) = result {
+                    tracing::debug!("Autocomplete for /{} failed: {}", interaction.data.name, e);
+                }
+                return;
+            }
+            Interaction::Component(component) => {
+                self.component(&ctx, &component).await;
+                return;
+            }
+            _ => return,
+        };
+
+        if !commands::is_enabled(&self.commands, command.guild_id, &command.data.name) {
+            if let Err(e) = commands::respond_disabled(&ctx, &command).await {
+                tracing::warn!("Could not refuse disabled /{}: {}", command.data.name, e);
+            }
+            return;
+        }
+
+        let timeout = Duration::from_secs(self.commands.timeout);
+        let result = match command.data.name.as_str() {
+            commands::about::NAME => {
+                let handler =
+                    commands::about::run(&ctx, &command, self.started, &self.about, &self.theme);
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::admin::NAME => {
+                let handler = commands::admin::run(&ctx, &command, &self.player, &self.theme);
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::history::NAME => {
+                let handler = commands::history::run(
+                    &ctx,
+                    &command,
+                    self.storage.as_ref(),
+                    &self.components,
+                    &self.theme,
+                );
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::library::NAME => {
+                let handler = commands::library::run(
+                    &ctx,
+                    &command,
+                    &self.player,
+                    self.library.as_ref(),
+                    &self.theme,
+                );
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::play::NAME => {
+                let handler = commands::play::run(&ctx, &command, &self.player, &self.theme);
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::playlist::NAME => {
+                let handler = commands::playlist::run(
+                    &ctx,
+                    &command,
+                    &self.player,
+                    self.storage.as_ref(),
+                    self.library.as_ref(),
+                    &self.theme,
+                );
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::controls::LOOP
+            | commands::controls::PAUSE
+            | commands::controls::RESUME
+            | commands::controls::SKIP
+            | commands::controls::STOP => {
+                let handler = commands::controls::run(&ctx, &command, &self.player, &self.theme);
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::queue::NAME => {
+                let handler = commands::queue::run(
+                    &ctx,
+                    &command,
+                    &self.player,
+                    &self.components,
+                    &self.theme,
+                );
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::remove::NAME => {
+                let handler = commands::remove::run(&ctx, &command, &self.player, &self.theme);
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::search::NAME => {
+                let handler = commands::search::run(&ctx, &command, &self.player, &self.theme);
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            commands::summon::SUMMON | commands::summon::MOVETO => {
+                let handler = commands::summon::run(&ctx, &command, &self.player, &self.theme);
+                commands::run_guarded(&ctx, &command, timeout, handler).await
+            }
+            name => {
+                tracing::warn!("Received unknown command /{}", name);
+                return;
+            }
+        };
+
+        if let Err(e) = result {
+            tracing::error!("Command /{} failed: {}", command.data.name, e);
+        }
+        // Replies sent through `followup` are already scheduled under their own kind
+        cleanup::schedule_response(&ctx, &command, cleanup::Kind::Reply).await;
+    }
+
+    async fn voice_state_update(&self, ctx: Context, _: Option<VoiceState>, new: VoiceState) {
+        let Some(guild_id) = new.guild_id else {
+            return;
+        };
+        if self.player.now_playing(guild_id).is_some() {
+            let streaming = player::ducking::someone_streaming(&ctx, guild_id);
+            self.player.set_streaming(guild_id, streaming);
+        }
+    }
+
+    async fn guild_create(&self, ctx: Context, guild: Guild, _: Option<bool>) {
+        if !access::is_allowed(&self.guilds, guild.id) {
+            access::leave(&ctx, &self.guilds, &guild).await;
+            return;
+        }
+
+        // Once per guild, not on every reconnect
+        let unregistered = self
+            .registered_guilds
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(guild.id);
+        if unregistered
+            && let Err(e) = commands::register_guild(&ctx, &self.commands, guild.id).await
+        {
+            tracing::error!(
+                "Failed to register slash commands in guild {}: {}",
+                guild.id,
+                e
+            );
+            // Retried on the next reconnect
+            self.registered_guilds
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .remove(&guild.id);
+        }
+
+        let bot_id = ctx.cache.current_user().id;
+        // Guild creates carry the bot's own member; asking Discord is the fallback
+        let member = match guild.members.get(&bot_id) {
+            Some(member) => member.clone(),
+            None => match guild.member(&ctx, bot_id).await {
+                Ok(member) => member.into_owned(),
+                Err(e) => {
+                    tracing::warn!("Could not audit permissions in guild {}: {}", guild.id, e);
+                    return;
+                }
+            },
+        };
+
+        let warnings = audit::audit_guild(&guild, &member);
+        if let Some(summary) = audit::summary(&warnings) {
+            tracing::warn!("Guild {} ({}): {}", guild.name, guild.id, summary);
+        }
+        for warning in warnings {
+            tracing::debug!("Guild {} ({}): {}", guild.name, guild.id, warning);
+        }
     }
 }
 
@@ -22,13 +342,56 @@ impl EventHandler for Handler {
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    // Printed before logging starts so the output can be redirected straight to a file
+    if let Some(Command::Service(command)) = &args.command {
+        let invocation = service::Invocation {
+            program: std::env::current_exe()?,
+            working_dir: std::env::current_dir()?,
+            config: args
+                .config
+                .as_deref()
+                .map(std::path::absolute)
+                .transpose()?,
+            profile: args.selected_profile(),
+        };
+        match command {
+            ServiceCommand::Launchd { label } => {
+                print!("{}", service::launchd_plist(label, &invocation));
+            }
+            ServiceCommand::ScheduledTask { name } => {
+                print!("{}", service::scheduled_task(name, &invocation));
+            }
+        }
+        return Ok(());
+    }
+
     let config = build_config(&args)?;
 
+    if args.print_invite {
+        let application_id = invite::application_id_from_token(&config.discord_token)
+            .ok_or("Cannot derive the application id from the configured Discord token")?;
+        println!("{}", invite::invite_url(application_id));
+        return Ok(());
+    }
+
+    if args.dry_run {
+        // Results go to stderr, keeping stdout for the commands
+        for passed in dry_run::check(&config).await? {
+            eprintln!("ok: {passed}");
+        }
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&commands::all(&config.commands))?
+        );
+        return Ok(());
+    }
+
     tracing_subscriber::fmt()
         .compact()
         .with_thread_names(true)
         .with_env_filter(tracing_subscriber::EnvFilter::new(&config.log_level))
         .init();
+    panic::install_hook();
 
     tracing::info!("config = {:?}", config);
 
@@ -38,28 +401,100 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .into(),
         );
     }
+    token::check_format(&config.discord_token)?;
+
+    let http = proxy::build_http(&config)?;
+    let bot = token::validate(&http).await?;
+    tracing::info!("Token belongs to {} ({})", bot.name, bot.id);
+
+    if let Some(Command::Announce {
+        guild,
+        channel,
+        message,
+        tts,
+    }) = args.command
+    {
+        return announce::announce(
+            &http,
+            GuildId::from(guild),
+            channel.map(ChannelId::from),
+            &message,
+            tts,
+        )
+        .await;
+    }
 
-    let intents = GatewayIntents::GUILD_MESSAGES
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::GUILD_VOICE_STATES
-        | GatewayIntents::MESSAGE_CONTENT;
-
-    let http = if let Some(ref api_url) = config.discord_api_url {
-        tracing::info!("Using custom Discord API URL: {}", api_url);
-        HttpBuilder::new(&config.discord_token)
-            .proxy(api_url)
-            .ratelimiter_disabled(true)
-            .build()
-    } else {
-        HttpBuilder::new(&config.discord_token).build()
+        | GatewayIntents::MESSAGE_CONTENT
+        | cache::intents(&config.cache);
+
+    let ytdlp = player::ytdlp::Ytdlp::new(&config.ytdlp)?;
+    let library = config
+        .music_library_path
+        .as_deref()
+        .map(player::library::Library::new)
+        .transpose()?;
+    let storage = match config.database_path.as_deref() {
+        Some(path) => Some(storage::Storage::open(path).await?),
+        None => None,
     };
+    let player = player::Player::new(
+        ytdlp,
+        player::sources::spotify::Spotify::new(&config.spotify),
+        config.player.clone(),
+    );
+    let events = player.subscribe();
+    if let Some(storage) = &storage {
+        history::spawn(storage.clone(), player.subscribe());
+    }
+    let (janitor, cleanup_queue) = cleanup::Janitor::new(config.cleanup.clone());
 
     let mut client = ClientBuilder::new_with_http(http, intents)
-        .event_handler(Handler)
+        .event_handler(Handler {
+            started: Instant::now(),
+            recent_interactions: std::sync::Mutex::new(dedupe::RecentIds::new(RECENT_INTERACTIONS)),
+            registered_guilds: std::sync::Mutex::new(HashSet::new()),
+            intents,
+            guilds: config.guilds.clone(),
+            about: config.about.clone(),
+            commands: config.commands.clone(),
+            theme: config.theme.clone(),
+            player,
+            components: commands::components::Components::new(&config.discord_token),
+            library,
+            storage,
+        })
+        .type_map_insert::<cleanup::Janitor>(janitor)
+        .cache_settings(cache::settings(&config.cache))
         .register_songbird()
         .await?;
 
-    tracing::info!("Starting Discord bot...");
-    client.start().await?;
+    if let Some(ref gateway_url) = config.discord.proxy.gateway_url {
+        tracing::info!("Using custom Discord gateway URL: {}", gateway_url);
+        *client.ws_url.lock().await = gateway_url.clone();
+    }
+
+    shutdown::shutdown_on_signal(client.shard_manager.clone());
+    presence::spawn(client.shard_manager.clone(), events);
+    cleanup::spawn(client.http.clone(), cleanup_queue);
 
-    Ok(())
+    if config.phone_home && config.updates.check {
+        update::spawn(client.http.clone(), config.updates.clone());
+    }
+
+    tracing::info!("Starting Discord bot...");
+    match client.start().await {
+        Err(serenity::Error::Gateway(GatewayError::DisallowedGatewayIntents)) => Err(
+            "Discord rejected the gateway intents. Enable the Message Content Intent \
+             (and Server Members / Presence Intent when cache.members / cache.presences \
+             are set) under Bot > Privileged Gateway Intents in the Discord Developer Portal"
+                .into(),
+        ),
+        Err(serenity::Error::Gateway(GatewayError::InvalidAuthentication)) => {
+            Err("Discord rejected the bot token. Check TRIBOFERRIN_DISCORD_TOKEN".into())
+        }
+        result => Ok(result?),
+    }
 }
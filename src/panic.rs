@@ -0,0 +1,69 @@
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::panic::{self, PanicHookInfo};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static PANICS: AtomicU64 = AtomicU64::new(0);
+
+/// Route panics through tracing and count them. Serenity runs each event in its own
+/// task, so a panicking handler is logged here instead of taking the client down. This is
+/// the one place panics are logged, with a backtrace when `RUST_BACKTRACE` asks for one.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info: &PanicHookInfo| {
+        let total = PANICS.fetch_add(1, Ordering::Relaxed) + 1;
+        let location = info
+            .location()
+            .map(|location| location.to_string())
+            .unwrap_or_else(|| "unknown location".to_string());
+        let backtrace = match Backtrace::capture() {
+            backtrace if backtrace.status() == BacktraceStatus::Captured => {
+                format!("\n{backtrace}")
+            }
+            _ => String::new(),
+        };
+        tracing::error!(
+            panics = total,
+            "Panic at {}: {}{}",
+            location,
+            message(info.payload()),
+            backtrace
+        );
+    }));
+}
+
+/// Number of panics since startup.
+pub fn count() -> u64 {
+    PANICS.load(Ordering::Relaxed)
+}
+
+/// Text of a panic payload, for the common `&str` and `String` cases.
+pub fn message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_str_payload() {
+        let payload: Box<dyn Any + Send> = Box::new("boom");
+        assert_eq!(message(payload.as_ref()), "boom");
+    }
+
+    #[test]
+    fn test_message_string_payload() {
+        let payload: Box<dyn Any + Send> = Box::new(format!("boom {}", 1));
+        assert_eq!(message(payload.as_ref()), "boom 1");
+    }
+
+    #[test]
+    fn test_message_other_payload() {
+        let payload: Box<dyn Any + Send> = Box::new(42);
+        assert_eq!(message(payload.as_ref()), "non-string panic payload");
+    }
+}
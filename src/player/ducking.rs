@@ -0,0 +1,122 @@
+//! Quieting playback while someone in the bot's voice channel is streaming.
+
+use serenity::all::{Context, GuildId};
+use songbird::tracks::TrackHandle;
+
+use crate::config::{PlayerConfig, StreamReaction};
+
+/// How a guild reacts to streams, honouring per-guild overrides.
+pub fn reaction(config: &PlayerConfig, guild_id: GuildId) -> StreamReaction {
+    config
+        .guilds
+        .get(&guild_id.to_string())
+        .copied()
+        .unwrap_or(config.on_stream)
+}
+
+/// Whether anyone but the bot is streaming in the voice channel the bot is in.
+pub fn someone_streaming(ctx: &Context, guild_id: GuildId) -> bool {
+    let bot_id = ctx.cache.current_user().id;
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return false;
+    };
+    let Some(channel_id) = guild
+        .voice_states
+        .get(&bot_id)
+        .and_then(|state| state.channel_id)
+    else {
+        return false;
+    };
+
+    guild.voice_states.values().any(|state| {
+        state.user_id != bot_id
+            && state.channel_id == Some(channel_id)
+            && state.self_stream == Some(true)
+    })
+}
+
+/// What to do to a track when a stream starts or ends.
+#[derive(Debug, PartialEq)]
+enum Adjustment {
+    Volume(f32),
+    Pause,
+    Play,
+}
+
+/// Quiet a track down (`quiet`) or bring it back, according to `reaction`. A track the
+/// user `paused` stays paused when the stream ends.
+pub fn apply(
+    config: &PlayerConfig,
+    reaction: StreamReaction,
+    handle: &TrackHandle,
+    quiet: bool,
+    paused: bool,
+) {
+    let result = match adjustment(config, reaction, quiet, paused) {
+        None => Ok(()),
+        Some(Adjustment::Volume(volume)) => handle.set_volume(volume),
+        Some(Adjustment::Pause) => handle.pause(),
+        Some(Adjustment::Play) => handle.play(),
+    };
+    if let Err(e) = result {
+        tracing::debug!("Could not adjust track for streaming: {}", e);
+    }
+}
+
+fn adjustment(
+    config: &PlayerConfig,
+    reaction: StreamReaction,
+    quiet: bool,
+    paused: bool,
+) -> Option<Adjustment> {
+    match (reaction, quiet) {
+        (StreamReaction::Off, _) => None,
+        (StreamReaction::Duck, true) => {
+            Some(Adjustment::Volume(f32::from(config.duck_volume) / 100.0))
+        }
+        (StreamReaction::Duck, false) => Some(Adjustment::Volume(1.0)),
+        (StreamReaction::Pause, true) => Some(Adjustment::Pause),
+        // Only undo the pause the stream caused
+        (StreamReaction::Pause, false) => (!paused).then_some(Adjustment::Play),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::collections::BTreeMap;
+
+    #[rstest]
+    #[case(111, StreamReaction::Pause)]
+    #[case(222, StreamReaction::Duck)]
+    fn test_reaction(#[case] guild_id: u64, #[case] expected: StreamReaction) {
+        let config = PlayerConfig {
+            on_stream: StreamReaction::Duck,
+            guilds: BTreeMap::from([("111".to_string(), StreamReaction::Pause)]),
+            ..Default::default()
+        };
+        assert_eq!(reaction(&config, GuildId::new(guild_id)), expected);
+    }
+
+    #[rstest]
+    #[case(StreamReaction::Pause, true, false, Some(Adjustment::Pause))]
+    #[case(StreamReaction::Pause, true, true, Some(Adjustment::Pause))]
+    #[case(StreamReaction::Pause, false, false, Some(Adjustment::Play))]
+    #[case(StreamReaction::Pause, false, true, None)]
+    #[case(StreamReaction::Duck, true, true, Some(Adjustment::Volume(0.2)))]
+    #[case(StreamReaction::Duck, false, true, Some(Adjustment::Volume(1.0)))]
+    #[case(StreamReaction::Off, false, false, None)]
+    fn test_adjustment(
+        #[case] reaction: StreamReaction,
+        #[case] quiet: bool,
+        #[case] paused: bool,
+        #[case] expected: Option<Adjustment>,
+    ) {
+        let config = PlayerConfig {
+            duck_volume: 20,
+            ..Default::default()
+        };
+        assert_eq!(adjustment(&config, reaction, quiet, paused), expected);
+    }
+}
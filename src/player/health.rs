@@ -0,0 +1,184 @@
+//! Recent lookup outcomes for each track source, so operators can tell when one of them
+//! (usually YouTube) is failing while the others work.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Lookups remembered per source.
+const WINDOW: usize = 20;
+/// Longest error message kept.
+const ERROR_LIMIT: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resolver {
+    Ytdlp,
+    SoundCloud,
+    Spotify,
+    /// Direct audio streams and radio
+    Stream,
+}
+
+impl Resolver {
+    pub const ALL: [Resolver; 4] = [
+        Resolver::Ytdlp,
+        Resolver::SoundCloud,
+        Resolver::Spotify,
+        Resolver::Stream,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Resolver::Ytdlp => "yt-dlp",
+            Resolver::SoundCloud => "SoundCloud sets",
+            Resolver::Spotify => "Spotify",
+            Resolver::Stream => "Direct streams",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct History {
+    /// Success and latency of the latest lookups, oldest first
+    recent: VecDeque<(bool, Duration)>,
+    last_error: Option<(Instant, String)>,
+}
+
+/// How a source has been doing over its latest lookups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub lookups: usize,
+    pub failures: usize,
+    pub average_latency: Option<Duration>,
+    /// The latest error and how long ago it happened
+    pub last_error: Option<(Duration, String)>,
+}
+
+impl Summary {
+    pub fn status(&self) -> &'static str {
+        match (self.lookups, self.failures * 4) {
+            (0, _) => "No lookups yet",
+            (lookups, failures) if failures < lookups => "Healthy",
+            (lookups, failures) if failures < lookups * 3 => "Degraded",
+            _ => "Failing",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Health {
+    sources: Mutex<HashMap<Resolver, History>>,
+}
+
+impl Health {
+    pub fn record(&self, resolver: Resolver, latency: Duration, result: Result<(), &str>) {
+        let mut sources = self
+            .sources
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let history = sources.entry(resolver).or_default();
+        if history.recent.len() == WINDOW {
+            history.recent.pop_front();
+        }
+        history.recent.push_back((result.is_ok(), latency));
+        if let Err(e) = result {
+            let message = match e.char_indices().nth(ERROR_LIMIT) {
+                Some((index, _)) => format!("{}…", &e[..index]),
+                None => e.to_string(),
+            };
+            history.last_error = Some((Instant::now(), message));
+        }
+    }
+
+    pub fn summary(&self, resolver: Resolver) -> Summary {
+        let sources = self
+            .sources
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(history) = sources.get(&resolver) else {
+            return Summary {
+                lookups: 0,
+                failures: 0,
+                average_latency: None,
+                last_error: None,
+            };
+        };
+
+        let lookups = history.recent.len();
+        let total: Duration = history.recent.iter().map(|(_, latency)| *latency).sum();
+        Summary {
+            lookups,
+            failures: history.recent.iter().filter(|(ok, _)| !ok).count(),
+            average_latency: u32::try_from(lookups)
+                .ok()
+                .filter(|lookups| *lookups > 0)
+                .map(|lookups| total / lookups),
+            last_error: history
+                .last_error
+                .as_ref()
+                .map(|(at, message)| (at.elapsed(), message.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_summary_without_lookups() {
+        let summary = Health::default().summary(Resolver::Ytdlp);
+        assert_eq!(summary.lookups, 0);
+        assert_eq!(summary.status(), "No lookups yet");
+    }
+
+    #[test]
+    fn test_summary() {
+        let health = Health::default();
+        health.record(Resolver::Ytdlp, Duration::from_millis(100), Ok(()));
+        health.record(Resolver::Ytdlp, Duration::from_millis(300), Err("HTTP 429"));
+        health.record(Resolver::Spotify, Duration::from_millis(50), Ok(()));
+
+        let summary = health.summary(Resolver::Ytdlp);
+        assert_eq!(summary.lookups, 2);
+        assert_eq!(summary.failures, 1);
+        assert_eq!(summary.average_latency, Some(Duration::from_millis(200)));
+        assert_eq!(
+            summary.last_error.map(|(_, message)| message).as_deref(),
+            Some("HTTP 429")
+        );
+        assert_eq!(health.summary(Resolver::Spotify).failures, 0);
+    }
+
+    #[test]
+    fn test_only_recent_lookups_count() {
+        let health = Health::default();
+        for _ in 0..WINDOW {
+            health.record(Resolver::Ytdlp, Duration::ZERO, Err("down"));
+        }
+        for _ in 0..WINDOW {
+            health.record(Resolver::Ytdlp, Duration::ZERO, Ok(()));
+        }
+
+        let summary = health.summary(Resolver::Ytdlp);
+        assert_eq!((summary.lookups, summary.failures), (WINDOW, 0));
+        assert!(summary.last_error.is_some());
+    }
+
+    #[rstest]
+    #[case(4, 0, "Healthy")]
+    #[case(4, 1, "Degraded")]
+    #[case(4, 2, "Degraded")]
+    #[case(4, 3, "Failing")]
+    #[case(1, 1, "Failing")]
+    fn test_status(#[case] lookups: usize, #[case] failures: usize, #[case] expected: &str) {
+        let summary = Summary {
+            lookups,
+            failures,
+            average_latency: None,
+            last_error: None,
+        };
+        assert_eq!(summary.status(), expected);
+    }
+}
@@ -0,0 +1,362 @@
+//! A local music directory, for bots running next to their owner's own collection.
+//!
+//! Users only ever name paths relative to the library root. Those paths may not be
+//! absolute or contain `..`, and are checked again after symlinks are resolved, so
+//! nothing outside the root can be listed or played.
+//!
+//! Resolving paths and walking folders can take a while on big libraries and network
+//! shares, so everything the commands call does it on the blocking thread pool.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use super::queue::{QueuedTrack, Source};
+
+/// Extensions of files the decoder can play.
+const AUDIO_EXTENSIONS: [&str; 10] = [
+    "aac", "flac", "m4a", "mka", "mp3", "oga", "ogg", "opus", "wav", "webm",
+];
+/// Most directory entries visited when searching, to bound the time spent on huge
+/// libraries and network shares.
+const SEARCH_VISIT_LIMIT: usize = 20_000;
+
+/// Folders and audio files directly inside a library folder, relative to the root.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Listing {
+    pub folders: Vec<String>,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Library {
+    root: PathBuf,
+}
+
+impl Library {
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let root = path
+            .canonicalize()
+            .map_err(|e| format!("music_library_path {}: {e}", path.display()))?;
+        if !root.is_dir() {
+            return Err(format!(
+                "music_library_path {} is not a directory",
+                path.display()
+            ));
+        }
+        Ok(Self { root })
+    }
+
+    /// An audio file inside the library.
+    pub async fn file(&self, relative: &str) -> Result<PathBuf, String> {
+        let relative = relative.to_string();
+        self.blocking(move |library| library.find_file(&relative))
+            .await?
+    }
+
+    /// Contents of a library folder, sorted by name. Hidden entries are left out.
+    pub async fn list(&self, relative: &str) -> Result<Listing, String> {
+        let relative = relative.to_string();
+        self.blocking(move |library| library.read_folder(&relative))
+            .await?
+    }
+
+    /// Up to `limit` audio files whose path contains every word of `query`, ignoring case.
+    pub async fn search(&self, query: &str, limit: usize) -> Vec<String> {
+        let query = query.to_string();
+        self.blocking(move |library| library.walk(&query, limit))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Run `task` on a copy of the library on the blocking thread pool.
+    async fn blocking<T: Send + 'static>(
+        &self,
+        task: impl FnOnce(&Library) -> T + Send + 'static,
+    ) -> Result<T, String> {
+        let library = self.clone();
+        tokio::task::spawn_blocking(move || task(&library))
+            .await
+            .map_err(|e| format!("Could not read the music library: {e}"))
+    }
+
+    /// Absolute path of `relative` inside the library, or an error if it is outside it
+    /// or does not exist.
+    fn resolve(&self, relative: &str) -> Result<PathBuf, String> {
+        let relative = relative.trim().trim_start_matches('/');
+        let outside = || format!("\"{relative}\" is not in the music library.");
+        if Path::new(relative)
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(outside());
+        }
+
+        let path = self
+            .root
+            .join(relative)
+            .canonicalize()
+            .map_err(|_| format!("\"{relative}\" was not found in the music library."))?;
+        if !path.starts_with(&self.root) {
+            return Err(outside());
+        }
+        Ok(path)
+    }
+
+    fn find_file(&self, relative: &str) -> Result<PathBuf, String> {
+        let path = self.resolve(relative)?;
+        if !path.is_file() || !is_audio(&path) {
+            return Err(format!("\"{}\" is not an audio file.", relative.trim()));
+        }
+        Ok(path)
+    }
+
+    /// A file saved earlier as an absolute path, if it is still an audio file inside the
+    /// library. It is checked again as if a user had named it, since the library may have
+    /// moved or changed since.
+    fn saved_file(&self, saved: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(saved)
+            .strip_prefix(&self.root)
+            .ok()
+            .and_then(Path::to_str)
+            .ok_or_else(|| format!("\"{saved}\" is not in the music library."))?;
+        self.find_file(relative)
+    }
+
+    fn read_folder(&self, relative: &str) -> Result<Listing, String> {
+        let dir = self.resolve(relative)?;
+        let entries = fs::read_dir(&dir).map_err(|e| format!("Could not read folder: {e}"))?;
+
+        let mut listing = Listing::default();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = self.relative(&path) else {
+                continue;
+            };
+            if is_hidden(&path) {
+                continue;
+            }
+            // Follows symlinks, which `resolve` checks when they are used
+            if path.is_dir() {
+                listing.folders.push(name);
+            } else if is_audio(&path) {
+                listing.files.push(name);
+            }
+        }
+        listing.folders.sort();
+        listing.files.sort();
+        Ok(listing)
+    }
+
+    fn walk(&self, query: &str, limit: usize) -> Vec<String> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut found = Vec::new();
+        let mut pending = vec![self.root.clone()];
+        let mut visited = 0;
+
+        'walk: while let Some(dir) = pending.pop() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                visited += 1;
+                if visited > SEARCH_VISIT_LIMIT || found.len() >= limit {
+                    break 'walk;
+                }
+                let path = entry.path();
+                // Symlinked folders are not followed, so loops can't trap the search
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                if is_hidden(&path) {
+                    continue;
+                }
+                if file_type.is_dir() {
+                    pending.push(path);
+                } else if is_audio(&path)
+                    && let Some(name) = self.relative(&path)
+                    && words
+                        .iter()
+                        .all(|word| name.to_lowercase().contains(word.as_str()))
+                {
+                    found.push(name);
+                }
+            }
+        }
+        found.sort();
+        found
+    }
+
+    fn relative(&self, path: &Path) -> Option<String> {
+        path.strip_prefix(&self.root)
+            .ok()?
+            .to_str()
+            .map(str::to_string)
+    }
+}
+
+/// Saved tracks that may still be played, and how many were left out. Library files
+/// must still be inside `library`; without one, none of them play.
+pub async fn playable(
+    library: Option<&Library>,
+    tracks: Vec<QueuedTrack>,
+) -> (Vec<QueuedTrack>, usize) {
+    let total = tracks.len();
+    let library = library.cloned();
+    tokio::task::spawn_blocking(move || {
+        let kept: Vec<QueuedTrack> = tracks
+            .into_iter()
+            .filter_map(|track| match track.source {
+                Source::File => {
+                    let path = library.as_ref()?.saved_file(&track.url).ok()?;
+                    Some(QueuedTrack {
+                        url: path.display().to_string(),
+                        ..track
+                    })
+                }
+                Source::Ytdlp | Source::Stream => Some(track),
+            })
+            .collect();
+        let left_out = total - kept.len();
+        (kept, left_out)
+    })
+    .await
+    .unwrap_or_else(|_| (Vec::new(), total))
+}
+
+/// Track title for a library file: its name without the extension.
+pub fn title(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn is_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            AUDIO_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    /// A library with `Artist/Album/01 Song.mp3`, `Artist/cover.jpg`, `Loose.FLAC`, a
+    /// hidden file and a `secret.mp3` next to (outside) the library.
+    fn library(name: &str) -> (PathBuf, Library) {
+        let base = std::env::temp_dir().join(format!("triboferrin_library_{name}"));
+        let root = base.join("music");
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(root.join("Artist/Album")).unwrap();
+        fs::write(root.join("Artist/Album/01 Song.mp3"), b"").unwrap();
+        fs::write(root.join("Artist/cover.jpg"), b"").unwrap();
+        fs::write(root.join("Loose.FLAC"), b"").unwrap();
+        fs::write(root.join(".hidden.mp3"), b"").unwrap();
+        fs::write(base.join("secret.mp3"), b"").unwrap();
+        let library = Library::new(&root).unwrap();
+        (base, library)
+    }
+
+    #[tokio::test]
+    async fn test_list() {
+        let (base, library) = library("list");
+        assert_eq!(
+            library.list("").await.unwrap(),
+            Listing {
+                folders: vec!["Artist".to_string()],
+                files: vec!["Loose.FLAC".to_string()],
+            }
+        );
+        assert_eq!(
+            library.list("Artist").await.unwrap(),
+            Listing {
+                folders: vec!["Artist/Album".to_string()],
+                files: vec![],
+            }
+        );
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[rstest]
+    #[case("../secret.mp3")]
+    #[case("Artist/../../secret.mp3")]
+    #[case("/etc/passwd")]
+    #[case("missing.mp3")]
+    #[case("Artist/cover.jpg")]
+    #[case("Artist")]
+    fn test_file_rejected(#[case] relative: &str) {
+        let (base, library) = library(&format!("rejected_{}", relative.replace(['/', '.'], "_")));
+        assert!(library.find_file(relative).is_err(), "{relative}");
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_file() {
+        let (base, library) = library("file");
+        let path = library.file("Artist/Album/01 Song.mp3").await.unwrap();
+        assert!(path.ends_with("Artist/Album/01 Song.mp3"));
+        assert_eq!(title(&path), "01 Song");
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_out_of_library_rejected() {
+        let (base, library) = library("symlink");
+        std::os::unix::fs::symlink(base.join("secret.mp3"), base.join("music/link.mp3")).unwrap();
+        assert!(library.find_file("link.mp3").is_err());
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_playable() {
+        let (base, library) = library("playable");
+        let track = |url: String, source| QueuedTrack {
+            url,
+            title: "t".to_string(),
+            duration: None,
+            requester: serenity::all::UserId::new(1),
+            source,
+        };
+        let song = library.find_file("Artist/Album/01 Song.mp3").unwrap();
+        let tracks = vec![
+            track(song.display().to_string(), Source::File),
+            track(base.join("secret.mp3").display().to_string(), Source::File),
+            track(
+                format!("{}/../secret.mp3", library.root.display()),
+                Source::File,
+            ),
+            track("https://example.com/a".to_string(), Source::Ytdlp),
+        ];
+
+        let (kept, left_out) = playable(Some(&library), tracks.clone()).await;
+        let urls: Vec<&str> = kept.iter().map(|track| track.url.as_str()).collect();
+        assert_eq!(urls, vec![song.to_str().unwrap(), "https://example.com/a"]);
+        assert_eq!(left_out, 2);
+
+        let (kept, left_out) = playable(None, tracks).await;
+        assert_eq!(kept.len(), 1);
+        assert_eq!(left_out, 3);
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[rstest]
+    #[case("song", vec!["Artist/Album/01 Song.mp3"])]
+    #[case("artist song", vec!["Artist/Album/01 Song.mp3"])]
+    #[case("loose", vec!["Loose.FLAC"])]
+    #[case("secret", vec![])]
+    #[case("hidden", vec![])]
+    fn test_search(#[case] query: &str, #[case] expected: Vec<&str>) {
+        let (base, library) = library(&format!("search-{}", query.replace(' ', "-")));
+        assert_eq!(library.walk(query, 10), expected);
+        fs::remove_dir_all(base).unwrap();
+    }
+}
@@ -0,0 +1,1157 @@
+//! Voice playback on top of songbird.
+//!
+//! Every guild plays independently: songbird gives each its own call and driver, and the
+//! state here is keyed by guild behind short, non-async critical sections. The only lock
+//! held across an await is the per-guild start lock, so a slow join in one guild never
+//! holds up another.
+
+pub mod ducking;
+pub mod health;
+pub mod library;
+pub mod queue;
+pub mod reference;
+pub mod search;
+pub mod sources;
+pub mod ytdlp;
+
+use serenity::all::{ChannelId, Context, GuildId, UserId};
+use songbird::input::{Compose, File, Input};
+use songbird::tracks::TrackHandle;
+use songbird::{Call, Event, EventContext, EventHandler as VoiceEventHandler, TrackEvent};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::config::PlayerConfig;
+use crate::events::{self, Bus};
+use health::{Health, Resolver};
+use queue::{QueuedTrack, Queues, Source};
+use sources::radio::{self, OnAir, Radio};
+use sources::soundcloud;
+use sources::spotify::{self, Spotify};
+use ytdlp::Ytdlp;
+
+const NOTHING_PLAYING: &str = "Nothing is playing.";
+
+/// What `/play` did with a track.
+pub enum Outcome {
+    Playing(QueuedTrack),
+    /// Added behind the current track, at this 1-based queue position
+    Queued(QueuedTrack, usize),
+}
+
+/// What happens when a track ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopMode {
+    /// Play the next queued track
+    #[default]
+    Off,
+    /// Play the same track again, until it is skipped
+    Track,
+    /// Put the track back at the end of the queue
+    Queue,
+}
+
+impl LoopMode {
+    pub const ALL: [LoopMode; 3] = [LoopMode::Off, LoopMode::Track, LoopMode::Queue];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LoopMode::Off => "off",
+            LoopMode::Track => "track",
+            LoopMode::Queue => "queue",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+struct NowPlaying {
+    track: QueuedTrack,
+    handle: TrackHandle,
+    /// Song announced by a radio stream
+    on_air: Option<OnAir>,
+    /// Stopped by `/skip`, so a looped track moves on anyway
+    skipped: bool,
+    /// Already queued again by `/skip` while looping the queue
+    requeued: bool,
+    /// Paused with `/pause`, which the end of a stream doesn't undo
+    paused: bool,
+}
+
+/// Per-guild playback state: the current track and the queue behind it.
+pub struct Player {
+    ytdlp: Ytdlp,
+    /// Resolves Spotify links, when credentials are configured
+    spotify: Option<Spotify>,
+    config: PlayerConfig,
+    pub queues: Queues,
+    pub health: Health,
+    events: Bus,
+    current: Mutex<HashMap<GuildId, NowPlaying>>,
+    /// Per-guild locks held while deciding between starting and queueing, so two
+    /// requests can't both start
+    starting: Mutex<HashMap<GuildId, Arc<AsyncMutex<()>>>>,
+    /// Guilds whose playback is ducked or paused because someone is streaming
+    quieted: Mutex<HashSet<GuildId>>,
+    /// Loop mode of guilds not using `LoopMode::Off`
+    loops: Mutex<HashMap<GuildId, LoopMode>>,
+}
+
+impl Player {
+    pub fn new(ytdlp: Ytdlp, spotify: Option<Spotify>, config: PlayerConfig) -> Arc<Self> {
+        let events = Bus::default();
+        Arc::new(Self {
+            ytdlp,
+            spotify,
+            config,
+            queues: Queues::new(events.clone()),
+            health: Health::default(),
+            events,
+            current: Mutex::new(HashMap::new()),
+            starting: Mutex::new(HashMap::new()),
+            quieted: Mutex::new(HashSet::new()),
+            loops: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn current(&self) -> std::sync::MutexGuard<'_, HashMap<GuildId, NowPlaying>> {
+        self.current
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn start_lock(&self, guild_id: GuildId) -> Arc<AsyncMutex<()>> {
+        let mut starting = self
+            .starting
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Arc::clone(starting.entry(guild_id).or_default())
+    }
+
+    fn quieted(&self) -> std::sync::MutexGuard<'_, HashSet<GuildId>> {
+        self.quieted
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Duck or pause playback while someone in the bot's channel streams, and restore it
+    /// once nobody does.
+    pub fn set_streaming(&self, guild_id: GuildId, streaming: bool) {
+        let reaction = ducking::reaction(&self.config, guild_id);
+        let mut quieted = self.quieted();
+        if quieted.contains(&guild_id) == streaming {
+            return;
+        }
+        if streaming {
+            quieted.insert(guild_id);
+        } else {
+            quieted.remove(&guild_id);
+        }
+
+        if let Some(playing) = self.current().get(&guild_id) {
+            tracing::info!(
+                "{} playback in guild {}: stream {}",
+                if streaming { "Quieting" } else { "Restoring" },
+                guild_id,
+                if streaming { "started" } else { "ended" }
+            );
+            ducking::apply(
+                &self.config,
+                reaction,
+                &playing.handle,
+                streaming,
+                playing.paused,
+            );
+        }
+    }
+
+    /// Receive playback events from now on.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<events::Event> {
+        self.events.subscribe()
+    }
+
+    pub fn ytdlp(&self) -> &Ytdlp {
+        &self.ytdlp
+    }
+
+    pub fn spotify_configured(&self) -> bool {
+        self.spotify.is_some()
+    }
+
+    /// The track currently playing in a guild. For radio streams the title includes the
+    /// song on air, when the station announces it.
+    pub fn now_playing(&self, guild_id: GuildId) -> Option<QueuedTrack> {
+        let current = self.current();
+        let playing = current.get(&guild_id)?;
+        let mut track = playing.track.clone();
+        if let Some(song) = playing.on_air.as_ref().and_then(OnAir::get) {
+            track.title = format!("{} - {}", track.title, song);
+        }
+        Some(track)
+    }
+
+    fn loops(&self) -> std::sync::MutexGuard<'_, HashMap<GuildId, LoopMode>> {
+        self.loops
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn loop_mode(&self, guild_id: GuildId) -> LoopMode {
+        self.loops().get(&guild_id).copied().unwrap_or_default()
+    }
+
+    /// Set what happens when tracks end in a guild, until changed or `/stop`.
+    pub fn set_loop_mode(&self, guild_id: GuildId, mode: LoopMode) {
+        match mode {
+            LoopMode::Off => self.loops().remove(&guild_id),
+            mode => self.loops().insert(guild_id, mode),
+        };
+    }
+
+    /// Pause the current track, until resumed even if a stream pauses and restores it.
+    pub fn pause(&self, guild_id: GuildId) -> Result<QueuedTrack, String> {
+        let mut current = self.current();
+        let playing = current.get_mut(&guild_id).ok_or(NOTHING_PLAYING)?;
+        playing.handle.pause().map_err(|e| e.to_string())?;
+        playing.paused = true;
+        Ok(playing.track.clone())
+    }
+
+    /// Resume the current track after a pause.
+    pub fn resume(&self, guild_id: GuildId) -> Result<QueuedTrack, String> {
+        let mut current = self.current();
+        let playing = current.get_mut(&guild_id).ok_or(NOTHING_PLAYING)?;
+        playing.handle.play().map_err(|e| e.to_string())?;
+        playing.paused = false;
+        Ok(playing.track.clone())
+    }
+
+    /// Stop the current track and drop `count - 1` queued ones after it, or move them to
+    /// the back behind it while the queue loops; the end event starts whatever is next.
+    /// Returns the stopped track, how many queued tracks were passed over and the one that
+    /// follows, if any.
+    pub fn skip(
+        &self,
+        guild_id: GuildId,
+        count: usize,
+    ) -> Result<(QueuedTrack, usize, Option<QueuedTrack>), String> {
+        let mut current = self.current();
+        let playing = current.get_mut(&guild_id).ok_or(NOTHING_PLAYING)?;
+        let passed = count.saturating_sub(1);
+        let dropped = if self.loop_mode(guild_id) == LoopMode::Queue {
+            // Looping, the skipped tracks come round again in their order
+            playing.requeued = true;
+            self.queues
+                .rotate_front(guild_id, passed, playing.track.clone())
+        } else {
+            self.queues.drop_front(guild_id, passed)
+        };
+        playing.handle.stop().map_err(|e| e.to_string())?;
+        playing.skipped = true;
+        Ok((playing.track.clone(), dropped, self.queues.peek(guild_id)))
+    }
+
+    /// Join `channel_id`, or move there if already connected in the guild. The current
+    /// track keeps playing from where it was and the queue is kept.
+    pub async fn move_to(
+        &self,
+        ctx: &Context,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> Result<(), String> {
+        let manager = songbird::get(ctx)
+            .await
+            .ok_or("Voice support is not initialized")?;
+        manager
+            .join(guild_id, channel_id)
+            .await
+            .map_err(|e| format!("Could not join <#{channel_id}>: {e}"))?;
+        self.events.publish(events::Event::VoiceConnected {
+            guild_id,
+            channel_id,
+        });
+        Ok(())
+    }
+
+    /// Stop playback, clear the queue and leave the voice channel, also when nothing is
+    /// playing. Returns the track that was playing, if any, and how many queued tracks
+    /// were dropped.
+    pub async fn stop(&self, ctx: &Context, guild_id: GuildId) -> (Option<QueuedTrack>, usize) {
+        let manager = songbird::get(ctx).await;
+        self.stop_leaving(guild_id, async || {
+            if let Some(manager) = manager
+                && manager.get(guild_id).is_some()
+                && let Err(e) = manager.leave(guild_id).await
+            {
+                tracing::warn!("Could not leave voice in guild {}: {}", guild_id, e);
+            }
+        })
+        .await
+    }
+
+    /// [`Player::stop`], leaving the voice channel with `leave`. Holds the start lock
+    /// throughout, so a track `/play` or `advance` is starting can't outlive the stop.
+    async fn stop_leaving(
+        &self,
+        guild_id: GuildId,
+        leave: impl AsyncFnOnce(),
+    ) -> (Option<QueuedTrack>, usize) {
+        let start_lock = self.start_lock(guild_id);
+        let _starting = start_lock.lock().await;
+        let (playing, cleared) = self.reset(guild_id);
+        if let Some(playing) = &playing
+            && let Err(e) = playing.handle.stop()
+        {
+            tracing::debug!("Track in guild {} already stopped: {}", guild_id, e);
+        }
+
+        leave().await;
+        self.events
+            .publish(events::Event::PlaybackStopped { guild_id });
+        (playing.map(|playing| playing.track), cleared)
+    }
+
+    /// Forget a guild's playback: the current track, the queue, the loop mode and
+    /// whether it is quieted for a stream. Callers hold the start lock.
+    fn reset(&self, guild_id: GuildId) -> (Option<NowPlaying>, usize) {
+        let playing = self.current().remove(&guild_id);
+        self.quieted().remove(&guild_id);
+        self.loops().remove(&guild_id);
+        (playing, self.queues.clear(guild_id))
+    }
+
+    /// Look a URL up: Spotify links through the Spotify API, SoundCloud sets track by
+    /// track, links to audio files as direct streams, anything else with yt-dlp.
+    pub async fn resolve(&self, url: &str, requester: UserId) -> Result<Vec<QueuedTrack>, String> {
+        let started = Instant::now();
+        let (resolver, result) = if spotify::is_spotify(url) {
+            (
+                Resolver::Spotify,
+                self.resolve_spotify(url, requester).await,
+            )
+        } else if soundcloud::is_set(url) {
+            (
+                Resolver::SoundCloud,
+                self.resolve_soundcloud_set(url, requester).await,
+            )
+        } else {
+            let probed = if radio::looks_like_stream(url) {
+                self.probe_stream(url, requester).await
+            } else {
+                Ok(None)
+            };
+            match probed {
+                Ok(Some(track)) => (Resolver::Stream, Ok(vec![track])),
+                Err(e) => (Resolver::Stream, Err(e)),
+                Ok(None) => (Resolver::Ytdlp, self.resolve_ytdlp(url, requester).await),
+            }
+        };
+        self.record(resolver, started, &result);
+        result
+    }
+
+    fn record(
+        &self,
+        resolver: Resolver,
+        started: Instant,
+        result: &Result<Vec<QueuedTrack>, String>,
+    ) {
+        self.health.record(
+            resolver,
+            started.elapsed(),
+            result.as_ref().map(|_| ()).map_err(String::as_str),
+        );
+    }
+
+    /// Look a URL up as a direct stream, such as internet radio, without falling back to
+    /// yt-dlp.
+    pub async fn resolve_stream(
+        &self,
+        url: &str,
+        requester: UserId,
+    ) -> Result<Vec<QueuedTrack>, String> {
+        let started = Instant::now();
+        let result = match self.probe_stream(url, requester).await {
+            Ok(Some(track)) => Ok(vec![track]),
+            Ok(None) => Err(format!(
+                "<{url}> is not an audio stream, or could not be reached."
+            )),
+            Err(e) => Err(e),
+        };
+        self.record(Resolver::Stream, started, &result);
+        result
+    }
+
+    /// The stream at `url`, if it serves audio directly. Fails for private addresses.
+    async fn probe_stream(
+        &self,
+        url: &str,
+        requester: UserId,
+    ) -> Result<Option<QueuedTrack>, String> {
+        radio::check_public(url).await?;
+        let Some(stream) = radio::probe(self.ytdlp.stream_client(), url).await else {
+            return Ok(None);
+        };
+        Ok(Some(QueuedTrack {
+            url: url.to_string(),
+            title: stream.name.unwrap_or_else(|| url.to_string()),
+            duration: None,
+            requester,
+            source: Source::Stream,
+        }))
+    }
+
+    async fn resolve_ytdlp(
+        &self,
+        url: &str,
+        requester: UserId,
+    ) -> Result<Vec<QueuedTrack>, String> {
+        let metadata = self
+            .ytdlp
+            .source(url)
+            .aux_metadata()
+            .await
+            .map_err(|e| format!("Could not load <{url}>: {e}"))?;
+
+        Ok(vec![QueuedTrack {
+            url: url.to_string(),
+            title: metadata.title.unwrap_or_else(|| url.to_string()),
+            duration: metadata.duration,
+            requester,
+            source: Source::Ytdlp,
+        }])
+    }
+
+    async fn resolve_soundcloud_set(
+        &self,
+        url: &str,
+        requester: UserId,
+    ) -> Result<Vec<QueuedTrack>, String> {
+        let tracks =
+            soundcloud::set_tracks(&self.ytdlp, url, self.config.max_playlist_tracks).await?;
+        if tracks.is_empty() {
+            return Err(format!("<{url}> has no tracks to play."));
+        }
+        Ok(tracks
+            .into_iter()
+            .map(|track| QueuedTrack {
+                url: track.url,
+                title: track.title,
+                duration: track.duration,
+                requester,
+                source: Source::Ytdlp,
+            })
+            .collect())
+    }
+
+    async fn resolve_spotify(
+        &self,
+        url: &str,
+        requester: UserId,
+    ) -> Result<Vec<QueuedTrack>, String> {
+        let spotify = self.spotify.as_ref().ok_or(
+            "Spotify links need `spotify.client_id` and `spotify.client_secret` configured.",
+        )?;
+        let link = spotify::Link::parse(url)
+            .ok_or("Only Spotify track, album and playlist links can be played.")?;
+
+        let tracks = spotify.tracks(&link).await?;
+        if tracks.is_empty() {
+            return Err(format!("<{url}> has no tracks to play."));
+        }
+        Ok(tracks
+            .into_iter()
+            .map(|track| QueuedTrack {
+                url: track.search_url(),
+                title: track.title(),
+                duration: Some(track.duration()),
+                requester,
+                source: Source::Ytdlp,
+            })
+            .collect())
+    }
+
+    /// Join `channel_id` and play the first of `tracks`, or queue it when something is
+    /// already playing. The rest are queued behind it, in order.
+    pub async fn play(
+        self: &Arc<Self>,
+        ctx: &Context,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        tracks: Vec<QueuedTrack>,
+    ) -> Result<Outcome, String> {
+        let manager = songbird::get(ctx)
+            .await
+            .ok_or("Voice support is not initialized")?;
+        self.play_joining(guild_id, channel_id, tracks, async || {
+            manager
+                .join(guild_id, channel_id)
+                .await
+                .map_err(|e| format!("Could not join <#{channel_id}>: {e}"))
+        })
+        .await
+    }
+
+    /// [`Player::play`], getting the call to start in from `join`, which only runs when
+    /// nothing is playing yet.
+    async fn play_joining(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        tracks: Vec<QueuedTrack>,
+        join: impl AsyncFnOnce() -> Result<Arc<AsyncMutex<Call>>, String>,
+    ) -> Result<Outcome, String> {
+        let mut tracks = tracks.into_iter();
+        let track = tracks.next().ok_or("Nothing to play.")?;
+        let start_lock = self.start_lock(guild_id);
+        let _starting = start_lock.lock().await;
+        if self.current().contains_key(&guild_id) {
+            let position = self.queues.enqueue(guild_id, track.clone());
+            for rest in tracks {
+                self.queues.enqueue(guild_id, rest);
+            }
+            return Ok(Outcome::Queued(track, position));
+        }
+
+        let call = join().await?;
+        self.events.publish(events::Event::VoiceConnected {
+            guild_id,
+            channel_id,
+        });
+
+        for rest in tracks {
+            self.queues.enqueue(guild_id, rest);
+        }
+        self.start(guild_id, call, track.clone()).await;
+        Ok(Outcome::Playing(track))
+    }
+
+    async fn start(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        call: Arc<AsyncMutex<Call>>,
+        track: QueuedTrack,
+    ) {
+        let (input, on_air) = match track.source {
+            Source::Ytdlp => (self.ytdlp.source(&track.url).into(), None),
+            Source::Stream => {
+                let on_air = OnAir::default();
+                let radio = Radio::new(
+                    self.ytdlp.stream_client().clone(),
+                    track.url.clone(),
+                    on_air.clone(),
+                );
+                (Input::Lazy(Box::new(radio)), Some(on_air))
+            }
+            Source::File => (File::new(track.url.clone()).into(), None),
+        };
+        let handle = call.lock().await.play_only_input(input);
+        if self.quieted().contains(&guild_id) {
+            let reaction = ducking::reaction(&self.config, guild_id);
+            ducking::apply(&self.config, reaction, &handle, true, false);
+        }
+
+        for event in [TrackEvent::End, TrackEvent::Error] {
+            let ended = TrackEnded {
+                player: Arc::clone(self),
+                guild_id,
+                call: Arc::clone(&call),
+                track: handle.clone(),
+                failed: event == TrackEvent::Error,
+            };
+            if let Err(e) = handle.add_event(Event::Track(event), ended) {
+                tracing::warn!("Could not watch track in guild {}: {}", guild_id, e);
+            }
+        }
+
+        tracing::info!(
+            "Playing \"{}\" in guild {} ({} queued, up next: {})",
+            track.title,
+            guild_id,
+            self.queues.len(guild_id),
+            self.queues
+                .peek(guild_id)
+                .map_or_else(|| "nothing".to_string(), |next| next.title)
+        );
+        self.current().insert(
+            guild_id,
+            NowPlaying {
+                track: track.clone(),
+                handle,
+                on_air,
+                skipped: false,
+                requeued: false,
+                paused: false,
+            },
+        );
+        self.events
+            .publish(events::Event::TrackStarted { guild_id, track });
+    }
+
+    /// Move on once the current track (`ended`) has finished: to the same track or the
+    /// next queued one, depending on the loop mode. Tracks that `failed` are not repeated.
+    /// Holds the start lock, so a `/play` arriving meanwhile queues rather than starts.
+    /// Returns whether `ended` was still the current track.
+    async fn advance(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        call: Arc<AsyncMutex<Call>>,
+        ended: &TrackHandle,
+        failed: bool,
+    ) -> bool {
+        let start_lock = self.start_lock(guild_id);
+        let _starting = start_lock.lock().await;
+        let finished = {
+            let mut current = self.current();
+            match current.get(&guild_id) {
+                Some(playing) if playing.handle.uuid() == ended.uuid() => current.remove(&guild_id),
+                // Already advanced, e.g. by the End event after an Error
+                _ => return false,
+            }
+        };
+        let Some(finished) = finished else {
+            return false;
+        };
+
+        let repeat = !failed && !finished.skipped;
+        let requeue = !failed && !finished.requeued;
+        match self.next_after(guild_id, finished.track, repeat, requeue) {
+            Some(next) => self.start(guild_id, call, next).await,
+            None => self
+                .events
+                .publish(events::Event::PlaybackStopped { guild_id }),
+        }
+        true
+    }
+
+    /// The track to play after `finished`, following the loop mode: the same one again
+    /// if it may `repeat` (it wasn't skipped and didn't fail), or the next queued one,
+    /// with `finished` queued behind the rest when looping the queue and it may `requeue`
+    /// (it didn't fail).
+    fn next_after(
+        &self,
+        guild_id: GuildId,
+        finished: QueuedTrack,
+        repeat: bool,
+        requeue: bool,
+    ) -> Option<QueuedTrack> {
+        match self.loop_mode(guild_id) {
+            LoopMode::Track if repeat => return Some(finished),
+            LoopMode::Queue if requeue => {
+                self.queues.enqueue(guild_id, finished);
+            }
+            _ => {}
+        }
+        self.queues.dequeue(guild_id)
+    }
+}
+
+/// Voice channel a member is currently connected to, according to the cache.
+pub fn voice_channel(ctx: &Context, guild_id: GuildId, user_id: UserId) -> Option<ChannelId> {
+    let guild = ctx.cache.guild(guild_id)?;
+    guild.voice_states.get(&user_id)?.channel_id
+}
+
+struct TrackEnded {
+    player: Arc<Player>,
+    guild_id: GuildId,
+    call: Arc<AsyncMutex<Call>>,
+    track: TrackHandle,
+    /// Watching the Error event rather than End
+    failed: bool,
+}
+
+#[serenity::async_trait]
+impl VoiceEventHandler for TrackEnded {
+    async fn act(&self, _: &EventContext<'_>) -> Option<Event> {
+        self.player
+            .advance(
+                self.guild_id,
+                Arc::clone(&self.call),
+                &self.track,
+                self.failed,
+            )
+            .await;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::YtdlpConfig;
+    use rstest::rstest;
+
+    pub(super) fn track(n: usize) -> QueuedTrack {
+        QueuedTrack {
+            url: format!("https://example.com/{n}"),
+            title: n.to_string(),
+            duration: None,
+            requester: UserId::new(1),
+            source: Source::Ytdlp,
+        }
+    }
+
+    pub(super) fn player() -> Arc<Player> {
+        Player::new(
+            Ytdlp::new(&YtdlpConfig::default()).unwrap(),
+            None,
+            PlayerConfig::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_start_locks_are_per_guild() {
+        let player = player();
+        let first = player.start_lock(GuildId::new(1));
+        let _held = first.lock().await;
+
+        assert!(player.start_lock(GuildId::new(2)).try_lock().is_ok());
+        assert!(player.start_lock(GuildId::new(1)).try_lock().is_err());
+    }
+
+    #[test]
+    fn test_loop_mode_names() {
+        for mode in LoopMode::ALL {
+            assert_eq!(LoopMode::from_name(mode.name()), Some(mode));
+        }
+        assert_eq!(LoopMode::from_name("all"), None);
+    }
+
+    #[tokio::test]
+    async fn test_loop_mode_is_per_guild() {
+        let player = player();
+        player.set_loop_mode(GuildId::new(1), LoopMode::Queue);
+        assert_eq!(player.loop_mode(GuildId::new(1)), LoopMode::Queue);
+        assert_eq!(player.loop_mode(GuildId::new(2)), LoopMode::Off);
+
+        player.set_loop_mode(GuildId::new(1), LoopMode::Off);
+        assert!(player.loops().is_empty());
+    }
+
+    /// A player in guild 1 with tracks 1 and 2 queued and `mode` set.
+    fn looping(mode: LoopMode) -> Arc<Player> {
+        let player = player();
+        for n in 1..=2 {
+            player.queues.enqueue(GuildId::new(1), track(n));
+        }
+        player.set_loop_mode(GuildId::new(1), mode);
+        player
+    }
+
+    #[rstest]
+    #[case(LoopMode::Off, true, true, Some(1), &[2])]
+    #[case(LoopMode::Track, true, true, Some(0), &[1, 2])]
+    // Skipped
+    #[case(LoopMode::Track, false, true, Some(1), &[2])]
+    #[case(LoopMode::Queue, true, true, Some(1), &[2, 0])]
+    #[case(LoopMode::Queue, false, true, Some(1), &[2, 0])]
+    // Failed
+    #[case(LoopMode::Track, false, false, Some(1), &[2])]
+    #[case(LoopMode::Queue, false, false, Some(1), &[2])]
+    #[tokio::test]
+    async fn test_next_after(
+        #[case] mode: LoopMode,
+        #[case] repeat: bool,
+        #[case] requeue: bool,
+        #[case] next: Option<usize>,
+        #[case] queued: &[usize],
+    ) {
+        let player = looping(mode);
+        let guild_id = GuildId::new(1);
+        assert_eq!(
+            player.next_after(guild_id, track(0), repeat, requeue),
+            next.map(track)
+        );
+        assert_eq!(
+            player.queues.list(guild_id),
+            queued.iter().copied().map(track).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queue_loop_rotates_a_lone_track() {
+        let player = player();
+        let guild_id = GuildId::new(1);
+        player.set_loop_mode(guild_id, LoopMode::Queue);
+        assert_eq!(
+            player.next_after(guild_id, track(0), true, true),
+            Some(track(0))
+        );
+        assert!(player.queues.list(guild_id).is_empty());
+    }
+
+    /// Ending the current track, the way its End event does.
+    async fn end_current(player: &Arc<Player>, call: &Arc<AsyncMutex<Call>>) -> Option<String> {
+        let guild_id = GuildId::new(1);
+        let handle = player.current().get(&guild_id)?.handle.clone();
+        assert!(
+            player
+                .advance(guild_id, Arc::clone(call), &handle, false)
+                .await
+        );
+        player.now_playing(guild_id).map(|track| track.title)
+    }
+
+    #[tokio::test]
+    async fn test_advance_follows_loop_mode() {
+        let guild_id = GuildId::new(1);
+        let player = looping(LoopMode::Track);
+        let call = Arc::new(AsyncMutex::new(Call::standalone(guild_id, UserId::new(1))));
+        player
+            .play_joining(guild_id, ChannelId::new(1), vec![track(0)], async || {
+                Ok(Arc::clone(&call))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(end_current(&player, &call).await.as_deref(), Some("0"));
+        assert_eq!(end_current(&player, &call).await.as_deref(), Some("0"));
+
+        player.set_loop_mode(guild_id, LoopMode::Queue);
+        assert_eq!(end_current(&player, &call).await.as_deref(), Some("1"));
+        assert_eq!(end_current(&player, &call).await.as_deref(), Some("2"));
+        assert_eq!(end_current(&player, &call).await.as_deref(), Some("0"));
+
+        player.set_loop_mode(guild_id, LoopMode::Off);
+        assert_eq!(end_current(&player, &call).await.as_deref(), Some("1"));
+        assert_eq!(end_current(&player, &call).await.as_deref(), Some("2"));
+        assert_eq!(end_current(&player, &call).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_skip_in_a_looping_queue_keeps_the_tracks() {
+        let guild_id = GuildId::new(1);
+        let player = looping(LoopMode::Queue);
+        player.queues.enqueue(guild_id, track(3));
+        let call = Arc::new(AsyncMutex::new(Call::standalone(guild_id, UserId::new(1))));
+        player
+            .play_joining(guild_id, ChannelId::new(1), vec![track(0)], async || {
+                Ok(Arc::clone(&call))
+            })
+            .await
+            .unwrap();
+
+        let (stopped, passed, next) = player.skip(guild_id, 2).unwrap();
+        assert_eq!((stopped, passed, next), (track(0), 1, Some(track(2))));
+        assert_eq!(end_current(&player, &call).await.as_deref(), Some("2"));
+        assert_eq!(
+            player.queues.list(guild_id),
+            vec![track(3), track(0), track(1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pause_remembered_for_streams() {
+        let guild_id = GuildId::new(1);
+        let player = player();
+        let call = Arc::new(AsyncMutex::new(Call::standalone(guild_id, UserId::new(1))));
+        player
+            .play_joining(guild_id, ChannelId::new(1), vec![track(0)], async || {
+                Ok(Arc::clone(&call))
+            })
+            .await
+            .unwrap();
+        let paused = || player.current().get(&guild_id).unwrap().paused;
+
+        player.set_streaming(guild_id, true);
+        assert!(!paused());
+        player.pause(guild_id).unwrap();
+        player.set_streaming(guild_id, false);
+        assert!(paused());
+        player.resume(guild_id).unwrap();
+        assert!(!paused());
+    }
+
+    #[tokio::test]
+    async fn test_streams_recorded_in_health() {
+        let player = player();
+        let url = "http://127.0.0.1:8000/live.mp3";
+        assert!(player.resolve(url, UserId::new(1)).await.is_err());
+        assert!(player.resolve_stream(url, UserId::new(1)).await.is_err());
+
+        let summary = player.health.summary(Resolver::Stream);
+        assert_eq!((summary.lookups, summary.failures), (2, 2));
+        assert_eq!(player.health.summary(Resolver::Ytdlp).lookups, 0);
+    }
+
+    #[tokio::test]
+    async fn test_reset_when_idle() {
+        let player = player();
+        let guild_id = GuildId::new(1);
+        for n in 0..2 {
+            player.queues.enqueue(guild_id, track(n));
+        }
+        player.set_loop_mode(guild_id, LoopMode::Queue);
+        player.set_streaming(guild_id, true);
+
+        let (playing, cleared) = player.reset(guild_id);
+        assert!(playing.is_none());
+        assert_eq!(cleared, 2);
+        assert!(player.queues.list(guild_id).is_empty());
+        assert_eq!(player.loop_mode(guild_id), LoopMode::Off);
+        assert!(player.quieted().is_empty());
+    }
+}
+
+#[cfg(all(test, feature = "stress"))]
+mod stress {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    const GUILDS: u64 = 64;
+    const OPERATIONS: usize = 2_000;
+    /// Guilds with a voice driver each, for the play and advance test
+    const PLAYING_GUILDS: u64 = 8;
+    /// Concurrent `/play` callers per guild
+    const REQUESTERS: u64 = 4;
+    const REQUESTS: usize = 200;
+
+    fn track(guild: u64, n: usize) -> QueuedTrack {
+        QueuedTrack {
+            url: format!("https://example.com/{guild}/{n}"),
+            title: n.to_string(),
+            duration: None,
+            requester: UserId::new(guild),
+            source: Source::Ytdlp,
+        }
+    }
+
+    /// Dozens of guilds hammering their queues and stream state at once must neither
+    /// stall nor leak entries between guilds.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn stress_concurrent_guild_players() {
+        let player = super::tests::player();
+
+        let workers = (1..=GUILDS).map(|guild| {
+            let player = Arc::clone(&player);
+            tokio::spawn(async move {
+                let guild_id = GuildId::new(guild);
+                let mut dequeued = 0;
+                for n in 0..OPERATIONS {
+                    player.queues.enqueue(guild_id, track(guild, n));
+                    player.set_streaming(guild_id, n % 7 == 0);
+                    if n % 3 == 0 {
+                        let next = player.queues.dequeue(guild_id).unwrap();
+                        assert_eq!(next, track(guild, dequeued));
+                        dequeued += 1;
+                    }
+                    let _start_lock = player.start_lock(guild_id).lock_owned().await;
+                    tokio::task::yield_now().await;
+                }
+
+                let remaining = player.queues.list(guild_id);
+                assert_eq!(remaining.len(), OPERATIONS - dequeued);
+                assert!(
+                    remaining
+                        .iter()
+                        .zip(dequeued..)
+                        .all(|(queued, n)| *queued == track(guild, n))
+                );
+            })
+        });
+
+        let all = futures::future::join_all(workers);
+        let results = tokio::time::timeout(Duration::from_secs(60), all)
+            .await
+            .expect("guild players stalled");
+        for result in results {
+            result.expect("guild player panicked");
+        }
+    }
+
+    /// `/play` racing tracks ending must neither drop nor repeat a track: every requested
+    /// track plays exactly once, in whichever order. Standalone calls never finish their
+    /// tracks, so the test ends them itself.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn stress_concurrent_play_and_advance() {
+        let player = super::tests::player();
+
+        let guilds = (1..=PLAYING_GUILDS).map(|guild| {
+            let player = Arc::clone(&player);
+            tokio::spawn(async move {
+                let guild_id = GuildId::new(guild);
+                let call = Arc::new(AsyncMutex::new(Call::standalone(guild_id, UserId::new(1))));
+                let requested = Arc::new(AtomicBool::new(false));
+
+                let requesters = (0..REQUESTERS).map(|requester| {
+                    let player = Arc::clone(&player);
+                    let call = Arc::clone(&call);
+                    tokio::spawn(async move {
+                        for n in 0..REQUESTS {
+                            let track = track(guild, requester as usize * REQUESTS + n);
+                            player
+                                .play_joining(
+                                    guild_id,
+                                    ChannelId::new(1),
+                                    vec![track],
+                                    async || Ok(Arc::clone(&call)),
+                                )
+                                .await
+                                .unwrap();
+                            tokio::task::yield_now().await;
+                        }
+                    })
+                });
+                let requesters = futures::future::join_all(requesters);
+
+                let ender = {
+                    let player = Arc::clone(&player);
+                    let call = Arc::clone(&call);
+                    let requested = Arc::clone(&requested);
+                    tokio::spawn(async move {
+                        let mut ended = Vec::new();
+                        loop {
+                            let playing = player
+                                .current()
+                                .get(&guild_id)
+                                .map(|playing| (playing.handle.clone(), playing.track.clone()));
+                            let Some((handle, track)) = playing else {
+                                if requested.load(Ordering::Acquire)
+                                    && player.queues.len(guild_id) == 0
+                                {
+                                    return ended;
+                                }
+                                tokio::task::yield_now().await;
+                                continue;
+                            };
+                            if player
+                                .advance(guild_id, Arc::clone(&call), &handle, false)
+                                .await
+                            {
+                                ended.push(track);
+                            }
+                            tokio::task::yield_now().await;
+                        }
+                    })
+                };
+
+                for result in requesters.await {
+                    result.expect("requester panicked");
+                }
+                requested.store(true, Ordering::Release);
+                let mut ended = ender.await.expect("ender panicked");
+
+                ended.sort_by(|a, b| a.url.cmp(&b.url));
+                let mut expected: Vec<QueuedTrack> = (0..REQUESTERS as usize * REQUESTS)
+                    .map(|n| track(guild, n))
+                    .collect();
+                expected.sort_by(|a, b| a.url.cmp(&b.url));
+                assert_eq!(ended, expected);
+            })
+        });
+
+        let all = futures::future::join_all(guilds);
+        let results = tokio::time::timeout(Duration::from_secs(60), all)
+            .await
+            .expect("play and advance stalled");
+        for result in results {
+            result.expect("guild player panicked");
+        }
+    }
+
+    /// `/stop` racing `/play` and tracks ending must leave nothing playing or queued: a
+    /// track `advance` was starting can't come back after the stop.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn stress_stop_during_advance() {
+        let player = super::tests::player();
+
+        let guilds = (1..=PLAYING_GUILDS).map(|guild| {
+            let player = Arc::clone(&player);
+            tokio::spawn(async move {
+                let guild_id = GuildId::new(guild);
+                let call = Arc::new(AsyncMutex::new(Call::standalone(guild_id, UserId::new(1))));
+                // Held for reading by requesters, so no new track arrives while a stop
+                // is checked
+                let stopping = Arc::new(tokio::sync::RwLock::new(()));
+                let done = Arc::new(AtomicBool::new(false));
+
+                let requesters = (0..REQUESTERS).map(|requester| {
+                    let player = Arc::clone(&player);
+                    let call = Arc::clone(&call);
+                    let stopping = Arc::clone(&stopping);
+                    tokio::spawn(async move {
+                        for n in 0..REQUESTS {
+                            let track = track(guild, requester as usize * REQUESTS + n);
+                            let _playing = stopping.read().await;
+                            player
+                                .play_joining(
+                                    guild_id,
+                                    ChannelId::new(1),
+                                    vec![track],
+                                    async || Ok(Arc::clone(&call)),
+                                )
+                                .await
+                                .unwrap();
+                        }
+                    })
+                });
+                let requesters = futures::future::join_all(requesters);
+
+                let ender = {
+                    let player = Arc::clone(&player);
+                    let call = Arc::clone(&call);
+                    let done = Arc::clone(&done);
+                    tokio::spawn(async move {
+                        while !done.load(Ordering::Acquire) {
+                            let handle = player
+                                .current()
+                                .get(&guild_id)
+                                .map(|playing| playing.handle.clone());
+                            if let Some(handle) = handle {
+                                player
+                                    .advance(guild_id, Arc::clone(&call), &handle, false)
+                                    .await;
+                            }
+                            tokio::task::yield_now().await;
+                        }
+                    })
+                };
+
+                let stopper = {
+                    let player = Arc::clone(&player);
+                    let call = Arc::clone(&call);
+                    let done = Arc::clone(&done);
+                    tokio::spawn(async move {
+                        while !done.load(Ordering::Acquire) {
+                            let _stopping = stopping.write().await;
+                            {
+                                // Holding the call a moment catches an advance in the
+                                // middle of starting a track, where it waits for it
+                                let _call = call.lock().await;
+                                tokio::time::sleep(Duration::from_micros(200)).await;
+                            }
+                            player.stop_leaving(guild_id, async || {}).await;
+                            // Give an advance caught mid-start time to finish
+                            tokio::time::sleep(Duration::from_millis(1)).await;
+                            assert!(player.now_playing(guild_id).is_none());
+                            assert_eq!(player.queues.len(guild_id), 0);
+                            drop(_stopping);
+                            tokio::task::yield_now().await;
+                        }
+                    })
+                };
+
+                for result in requesters.await {
+                    result.expect("requester panicked");
+                }
+                done.store(true, Ordering::Release);
+                ender.await.expect("ender panicked");
+                stopper.await.expect("stop was undone");
+            })
+        });
+
+        let all = futures::future::join_all(guilds);
+        let results = tokio::time::timeout(Duration::from_secs(60), all)
+            .await
+            .expect("stop, play and advance stalled");
+        for result in results {
+            result.expect("guild player panicked");
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use serenity::all::{GuildId, UserId};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::events::{Bus, Event};
+
+/// A resolved track waiting to be played.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedTrack {
+    pub url: String,
+    pub title: String,
+    pub duration: Option<Duration>,
+    pub requester: UserId,
+    pub source: Source,
+}
+
+/// Where a track's audio comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// Extracted by yt-dlp when the track starts
+    Ytdlp,
+    /// Fetched directly, e.g. internet radio
+    Stream,
+    /// A file from the music library, by absolute path
+    File,
+}
+
+/// Pending tracks for every guild, in play order.
+#[derive(Debug, Default)]
+pub struct Queues {
+    guilds: Mutex<HashMap<GuildId, VecDeque<QueuedTrack>>>,
+    events: Bus,
+}
+
+impl Queues {
+    pub fn new(events: Bus) -> Self {
+        Self {
+            guilds: Mutex::default(),
+            events,
+        }
+    }
+
+    fn changed(&self, guild_id: GuildId, length: usize) {
+        self.events
+            .publish(Event::QueueChanged { guild_id, length });
+    }
+
+    fn with_queue<T>(
+        &self,
+        f: impl FnOnce(&mut HashMap<GuildId, VecDeque<QueuedTrack>>) -> T,
+    ) -> T {
+        let mut guilds = self
+            .guilds
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut guilds)
+    }
+
+    /// Append a track, returning its 1-based position in the queue.
+    pub fn enqueue(&self, guild_id: GuildId, track: QueuedTrack) -> usize {
+        let position = self.with_queue(|guilds| {
+            let queue = guilds.entry(guild_id).or_default();
+            queue.push_back(track);
+            queue.len()
+        });
+        self.changed(guild_id, position);
+        position
+    }
+
+    /// Take the next track to play.
+    pub fn dequeue(&self, guild_id: GuildId) -> Option<QueuedTrack> {
+        let (track, length) = self.with_queue(|guilds| {
+            let queue = guilds.get_mut(&guild_id)?;
+            let track = queue.pop_front()?;
+            let length = queue.len();
+            if queue.is_empty() {
+                guilds.remove(&guild_id);
+            }
+            Some((track, length))
+        })?;
+        self.changed(guild_id, length);
+        Some(track)
+    }
+
+    /// The next track to play, without removing it.
+    pub fn peek(&self, guild_id: GuildId) -> Option<QueuedTrack> {
+        self.with_queue(|guilds| guilds.get(&guild_id)?.front().cloned())
+    }
+
+    pub fn len(&self, guild_id: GuildId) -> usize {
+        self.with_queue(|guilds| guilds.get(&guild_id).map_or(0, VecDeque::len))
+    }
+
+    /// Copy of the pending tracks, in play order.
+    pub fn list(&self, guild_id: GuildId) -> Vec<QueuedTrack> {
+        self.with_queue(|guilds| {
+            guilds
+                .get(&guild_id)
+                .map(|queue| queue.iter().cloned().collect())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Remove the track `pick` chooses by index, deciding and removing under one lock so
+    /// the queue can't change in between. Returns the track and its 1-based position.
+    pub fn remove_with<E>(
+        &self,
+        guild_id: GuildId,
+        pick: impl FnOnce(&[QueuedTrack]) -> Result<usize, E>,
+    ) -> Result<(usize, QueuedTrack), E> {
+        let (position, track, length) = self.with_queue(|guilds| {
+            let queue = guilds.entry(guild_id).or_default();
+            let index = pick(queue.make_contiguous())?;
+            let track = queue.remove(index);
+            let length = queue.len();
+            if queue.is_empty() {
+                guilds.remove(&guild_id);
+            }
+            Ok((
+                index + 1,
+                track.expect("pick returned an index outside the queue"),
+                length,
+            ))
+        })?;
+        self.changed(guild_id, length);
+        Ok((position, track))
+    }
+
+    /// Drop up to `count` tracks from the front, returning how many were dropped.
+    pub fn drop_front(&self, guild_id: GuildId, count: usize) -> usize {
+        let (dropped, length) = self.with_queue(|guilds| {
+            let Some(queue) = guilds.get_mut(&guild_id) else {
+                return (0, 0);
+            };
+            let dropped = count.min(queue.len());
+            queue.drain(..dropped);
+            let length = queue.len();
+            if queue.is_empty() {
+                guilds.remove(&guild_id);
+            }
+            (dropped, length)
+        });
+        if dropped > 0 {
+            self.changed(guild_id, length);
+        }
+        dropped
+    }
+
+    /// Move up to `count` tracks from the front to the back, behind `finished`: skipping
+    /// them while the queue loops. Returns how many were moved.
+    pub fn rotate_front(&self, guild_id: GuildId, count: usize, finished: QueuedTrack) -> usize {
+        let (moved, length) = self.with_queue(|guilds| {
+            let queue = guilds.entry(guild_id).or_default();
+            let moved = count.min(queue.len());
+            let skipped: Vec<QueuedTrack> = queue.drain(..moved).collect();
+            queue.push_back(finished);
+            queue.extend(skipped);
+            (moved, queue.len())
+        });
+        self.changed(guild_id, length);
+        moved
+    }
+
+    /// Drop every pending track, returning how many there were.
+    pub fn clear(&self, guild_id: GuildId) -> usize {
+        let cleared =
+            self.with_queue(|guilds| guilds.remove(&guild_id).map_or(0, |queue| queue.len()));
+        if cleared > 0 {
+            self.changed(guild_id, 0);
+        }
+        cleared
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str) -> QueuedTrack {
+        QueuedTrack {
+            url: format!("https://example.com/{title}"),
+            title: title.to_string(),
+            duration: Some(Duration::from_secs(180)),
+            requester: UserId::new(1),
+            source: Source::Ytdlp,
+        }
+    }
+
+    #[test]
+    fn test_queue_is_fifo() {
+        let queues = Queues::default();
+        let guild = GuildId::new(1);
+        assert_eq!(queues.enqueue(guild, track("a")), 1);
+        assert_eq!(queues.enqueue(guild, track("b")), 2);
+
+        assert_eq!(queues.len(guild), 2);
+        assert_eq!(queues.list(guild), vec![track("a"), track("b")]);
+        assert_eq!(queues.peek(guild), Some(track("a")));
+        assert_eq!(queues.dequeue(guild), Some(track("a")));
+        assert_eq!(queues.dequeue(guild), Some(track("b")));
+        assert_eq!(queues.dequeue(guild), None);
+        assert_eq!(queues.len(guild), 0);
+    }
+
+    #[test]
+    fn test_clear() {
+        let queues = Queues::default();
+        let guild = GuildId::new(1);
+        queues.enqueue(guild, track("a"));
+        queues.enqueue(guild, track("b"));
+        queues.enqueue(GuildId::new(2), track("c"));
+
+        assert_eq!(queues.clear(guild), 2);
+        assert_eq!(queues.clear(guild), 0);
+        assert_eq!(queues.len(guild), 0);
+        assert_eq!(queues.len(GuildId::new(2)), 1);
+    }
+
+    #[test]
+    fn test_remove_with() {
+        let queues = Queues::default();
+        let guild = GuildId::new(1);
+        queues.enqueue(guild, track("a"));
+        queues.enqueue(guild, track("b"));
+        queues.enqueue(guild, track("c"));
+
+        assert_eq!(
+            queues.remove_with(guild, |_| Ok::<_, ()>(1)),
+            Ok((2, track("b")))
+        );
+        assert_eq!(queues.remove_with(guild, |_| Err("no")), Err("no"));
+        assert_eq!(queues.list(guild), vec![track("a"), track("c")]);
+    }
+
+    #[test]
+    fn test_drop_front() {
+        let queues = Queues::default();
+        let guild = GuildId::new(1);
+        queues.enqueue(guild, track("a"));
+        queues.enqueue(guild, track("b"));
+        queues.enqueue(guild, track("c"));
+
+        assert_eq!(queues.drop_front(guild, 2), 2);
+        assert_eq!(queues.list(guild), vec![track("c")]);
+        assert_eq!(queues.drop_front(guild, 5), 1);
+        assert_eq!(queues.drop_front(guild, 1), 0);
+        assert_eq!(queues.len(guild), 0);
+    }
+
+    #[test]
+    fn test_rotate_front() {
+        let queues = Queues::default();
+        let guild = GuildId::new(1);
+        queues.enqueue(guild, track("a"));
+        queues.enqueue(guild, track("b"));
+        queues.enqueue(guild, track("c"));
+
+        assert_eq!(queues.rotate_front(guild, 2, track("x")), 2);
+        assert_eq!(
+            queues.list(guild),
+            vec![track("c"), track("x"), track("a"), track("b")]
+        );
+        assert_eq!(queues.rotate_front(guild, 0, track("y")), 0);
+        assert_eq!(queues.len(guild), 5);
+    }
+
+    #[test]
+    fn test_changes_are_published() {
+        let events = Bus::default();
+        let mut received = events.subscribe();
+        let queues = Queues::new(events);
+        let guild = GuildId::new(1);
+        queues.enqueue(guild, track("a"));
+        queues.enqueue(guild, track("b"));
+        queues.dequeue(guild);
+        queues.drop_front(guild, 0);
+        queues.clear(guild);
+        queues.clear(guild);
+
+        let lengths: Vec<usize> = std::iter::from_fn(|| match received.try_recv() {
+            Ok(Event::QueueChanged { length, .. }) => Some(length),
+            _ => None,
+        })
+        .collect();
+        assert_eq!(lengths, vec![1, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_queues_are_per_guild() {
+        let queues = Queues::default();
+        queues.enqueue(GuildId::new(1), track("a"));
+        queues.enqueue(GuildId::new(2), track("b"));
+
+        assert_eq!(queues.peek(GuildId::new(1)), Some(track("a")));
+        assert_eq!(queues.peek(GuildId::new(2)), Some(track("b")));
+        assert_eq!(queues.len(GuildId::new(3)), 0);
+    }
+}
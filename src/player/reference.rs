@@ -0,0 +1,161 @@
+//! Finding a queued track from what a user typed: a position such as `3`, `#3`, `third`,
+//! `next` or `last`, or part of its title such as `the daft punk one`.
+
+use crate::player::queue::QueuedTrack;
+
+/// Lowest Jaro-Winkler similarity for a title to count as a fuzzy match.
+const FUZZY_THRESHOLD: f64 = 0.85;
+
+const ORDINALS: [&str; 10] = [
+    "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth",
+];
+
+/// Which queued tracks a reference picks, as 0-based queue indexes.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Match {
+    One(usize),
+    /// Several tracks fit equally well, in queue order
+    Ambiguous(Vec<usize>),
+    None,
+}
+
+/// Resolve `reference` against the pending `tracks`.
+pub fn find(reference: &str, tracks: &[QueuedTrack]) -> Match {
+    let text = normalize(reference);
+    if text.is_empty() {
+        return Match::None;
+    }
+    if let Some(position) = position(&text, tracks.len()) {
+        return if (1..=tracks.len()).contains(&position) {
+            Match::One(position - 1)
+        } else {
+            Match::None
+        };
+    }
+
+    let titles: Vec<String> = tracks
+        .iter()
+        .map(|track| track.title.to_lowercase())
+        .collect();
+    let mut matches: Vec<usize> = titles
+        .iter()
+        .enumerate()
+        .filter(|(_, title)| title.contains(&text))
+        .map(|(index, _)| index)
+        .collect();
+    if matches.is_empty() {
+        matches = titles
+            .iter()
+            .enumerate()
+            .filter(|(_, title)| similarity(&text, title) >= FUZZY_THRESHOLD)
+            .map(|(index, _)| index)
+            .collect();
+    }
+
+    match matches.as_slice() {
+        [] => Match::None,
+        [index] => Match::One(*index),
+        _ => Match::Ambiguous(matches),
+    }
+}
+
+/// Lowercase and drop filler such as "the ... one" or "... song".
+fn normalize(reference: &str) -> String {
+    let text = reference.trim().to_lowercase();
+    let text = text.strip_prefix("the ").unwrap_or(&text);
+    let text = ["one", "track", "song"]
+        .iter()
+        .find_map(|suffix| text.strip_suffix(suffix)?.strip_suffix(' '))
+        .unwrap_or(text);
+    text.trim().to_string()
+}
+
+/// 1-based position for numeric and ordinal references.
+fn position(text: &str, len: usize) -> Option<usize> {
+    let text = text.strip_prefix('#').unwrap_or(text);
+    if let Ok(number) = text.parse() {
+        return Some(number);
+    }
+    match text {
+        "next" => return Some(1),
+        "last" => return Some(len),
+        _ => {}
+    }
+    if let Some(index) = ORDINALS.iter().position(|ordinal| *ordinal == text) {
+        return Some(index + 1);
+    }
+    // 1st, 2nd, 3rd, 4th, ...
+    ["st", "nd", "rd", "th"]
+        .iter()
+        .find_map(|suffix| text.strip_suffix(suffix)?.parse().ok())
+}
+
+/// Best similarity between `text` and any run of the title's words of the same length,
+/// so a misspelled artist still matches a long title.
+fn similarity(text: &str, title: &str) -> f64 {
+    let words: Vec<&str> = title.split_whitespace().collect();
+    let width = text.split_whitespace().count().clamp(1, words.len().max(1));
+    words
+        .windows(width)
+        .map(|window| strsim::jaro_winkler(text, &window.join(" ")))
+        .fold(strsim::jaro_winkler(text, title), f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::queue::Source;
+    use rstest::rstest;
+    use serenity::all::UserId;
+
+    fn tracks() -> Vec<QueuedTrack> {
+        [
+            "Daft Punk - One More Time",
+            "Justice - D.A.N.C.E.",
+            "Daft Punk - Around the World",
+            "Air - La femme d'argent",
+        ]
+        .iter()
+        .map(|title| QueuedTrack {
+            url: format!("ytsearch1:{title}"),
+            title: title.to_string(),
+            duration: None,
+            requester: UserId::new(1),
+            source: Source::Ytdlp,
+        })
+        .collect()
+    }
+
+    #[rstest]
+    #[case("2", Match::One(1))]
+    #[case("#3", Match::One(2))]
+    #[case("the third one", Match::One(2))]
+    #[case("2nd", Match::One(1))]
+    #[case("next", Match::One(0))]
+    #[case("the last track", Match::One(3))]
+    #[case("5", Match::None)]
+    #[case("0", Match::None)]
+    #[case("justice", Match::One(1))]
+    #[case("the around the world one", Match::One(2))]
+    #[case("the Daft Punk one", Match::Ambiguous(vec![0, 2]))]
+    #[case("justise", Match::One(1))]
+    #[case("beethoven", Match::None)]
+    #[case("  ", Match::None)]
+    fn test_find(#[case] reference: &str, #[case] expected: Match) {
+        assert_eq!(find(reference, &tracks()), expected);
+    }
+
+    #[test]
+    fn test_find_in_empty_queue() {
+        assert_eq!(find("last", &[]), Match::None);
+        assert_eq!(find("daft punk", &[]), Match::None);
+    }
+
+    #[rstest]
+    #[case("The Daft Punk one", "daft punk")]
+    #[case("the last song", "last")]
+    #[case("someone", "someone")]
+    fn test_normalize(#[case] reference: &str, #[case] expected: &str) {
+        assert_eq!(normalize(reference), expected);
+    }
+}
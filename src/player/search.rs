@@ -0,0 +1,95 @@
+//! YouTube search through yt-dlp.
+
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::player::ytdlp::Ytdlp;
+
+/// A search hit, cheap to get because yt-dlp only lists results without extracting them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub duration: Option<Duration>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    title: Option<String>,
+    url: Option<String>,
+    duration: Option<f64>,
+}
+
+/// Top `limit` YouTube matches for `query`. The yt-dlp process is killed if the
+/// returned future is dropped, e.g. by a timeout.
+pub async fn search(ytdlp: &Ytdlp, query: &str, limit: usize) -> Result<Vec<SearchResult>, String> {
+    let output = ytdlp
+        .command()
+        .args(["-j", "--flat-playlist", "--no-warnings"])
+        .arg(format!("ytsearch{limit}:{query}"))
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Could not run yt-dlp: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "yt-dlp search failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(parse_results(&output.stdout))
+}
+
+/// Parse yt-dlp's one-JSON-object-per-line output, skipping entries without a title or URL.
+fn parse_results(stdout: &[u8]) -> Vec<SearchResult> {
+    stdout
+        .split(|&byte| byte == b'\n')
+        .filter_map(|line| serde_json::from_slice::<Entry>(line).ok())
+        .filter_map(|entry| {
+            Some(SearchResult {
+                title: entry.title?,
+                url: entry.url?,
+                duration: entry
+                    .duration
+                    .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                    .map(Duration::from_secs_f64),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_results() {
+        let stdout =
+            br#"{"title": "One", "url": "https://www.youtube.com/watch?v=1", "duration": 61.0}
+{"title": "Two", "url": "https://www.youtube.com/watch?v=2", "duration": null}
+{"title": "No url"}
+not json
+"#;
+        assert_eq!(
+            parse_results(stdout),
+            vec![
+                SearchResult {
+                    title: "One".to_string(),
+                    url: "https://www.youtube.com/watch?v=1".to_string(),
+                    duration: Some(Duration::from_secs(61)),
+                },
+                SearchResult {
+                    title: "Two".to_string(),
+                    url: "https://www.youtube.com/watch?v=2".to_string(),
+                    duration: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_results_empty() {
+        assert!(parse_results(b"").is_empty());
+    }
+}
@@ -0,0 +1,6 @@
+//! Links that don't map to a single yt-dlp track: services yt-dlp can't play, resolved
+//! into tracks it can, and playlists it would cut short, expanded into their tracks.
+
+pub mod radio;
+pub mod soundcloud;
+pub mod spotify;
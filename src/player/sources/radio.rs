@@ -0,0 +1,591 @@
+//! Direct audio streams such as Icecast or Shoutcast radio, and plain MP3 or AAC files.
+//!
+//! These are fetched without yt-dlp. Streams are requested with ICY metadata, which the
+//! server interleaves with the audio every `icy-metaint` bytes; the blocks are stripped
+//! before decoding and their `StreamTitle` is kept as the song currently on air.
+
+use futures::Stream;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{CONTENT_TYPE, HeaderMap};
+use reqwest::redirect::Policy;
+use reqwest::{ClientBuilder, Url};
+use serenity::async_trait;
+use songbird::input::core::io::MediaSource;
+use songbird::input::core::probe::Hint;
+use songbird::input::{
+    AsyncAdapterStream, AsyncMediaSource, AudioStream, AudioStreamError, AuxMetadata, Compose,
+};
+use std::io::{Error as IoError, ErrorKind, SeekFrom};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+/// How long to wait for a server's headers when checking whether a URL is a stream.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// File extensions of links probed as direct streams without being asked to.
+const STREAM_EXTENSIONS: [&str; 9] = [
+    "aac", "aacp", "flac", "m4a", "mp3", "oga", "ogg", "opus", "wav",
+];
+/// Redirects followed when fetching a stream.
+const MAX_REDIRECTS: usize = 10;
+const NOT_PUBLIC: &str = "Links to private or local addresses can't be played.";
+/// Bytes buffered between the network and the decoder.
+const BUFFER_LEN: usize = 64 * 1024;
+
+/// A direct stream found by `probe`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Station name from `icy-name`, if the server is a radio server
+    pub name: Option<String>,
+}
+
+/// The song a radio stream is playing, updated as metadata arrives.
+#[derive(Debug, Clone, Default)]
+pub struct OnAir(Arc<Mutex<Option<String>>>);
+
+impl OnAir {
+    pub fn get(&self) -> Option<String> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    fn set(&self, title: String) {
+        *self
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(title);
+    }
+}
+
+/// Whether `url` names an audio file by its extension, making it worth probing.
+pub fn looks_like_stream(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| {
+        url.path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .and_then(|file| file.rsplit_once('.'))
+            .is_some_and(|(_, extension)| {
+                STREAM_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+            })
+    })
+}
+
+/// Whether `url` serves audio directly rather than a page for yt-dlp to extract. Any
+/// failure counts as "no", leaving the URL to yt-dlp. `client` should be built with
+/// [`public_only`].
+pub async fn probe(client: &reqwest::Client, url: &str) -> Option<StreamInfo> {
+    let response = client
+        .get(url)
+        .header("Icy-MetaData", "1")
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    // The body is never read, so dropping the response closes the stream
+    is_stream(response.headers()).then(|| StreamInfo {
+        name: header(response.headers(), "icy-name"),
+    })
+}
+
+/// Refuse links to hosts on the bot's own network, such as `localhost`, `10.0.0.1` or
+/// names resolving to private addresses.
+pub async fn check_public(url: &str) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|_| format!("<{url}> is not a valid link."))?;
+    if !is_public_host(&url) {
+        return Err(NOT_PUBLIC.to_string());
+    }
+    if let Some(domain) = url.domain() {
+        let addresses = tokio::net::lookup_host((domain, 0))
+            .await
+            .map_err(|e| format!("Could not look up {domain}: {e}"))?;
+        if !addresses.into_iter().all(|address| is_public(address.ip())) {
+            return Err(NOT_PUBLIC.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Restrict a client to public addresses: names resolving to private ones fail to
+/// connect, and redirects to private addresses are not followed. Guards against an
+/// address changing between [`check_public`] and the request.
+pub fn public_only(builder: ClientBuilder) -> ClientBuilder {
+    builder
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if is_public_host(attempt.url()) {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        }))
+}
+
+/// Whether a URL's host may be public: a public IP address, or a name other than
+/// `localhost`. Names are checked once resolved.
+fn is_public_host(url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    // IPv6 hosts come bracketed
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => is_public(ip),
+        Err(_) => {
+            let host = host.trim_end_matches('.').to_ascii_lowercase();
+            host != "localhost" && !host.ends_with(".localhost")
+        }
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // Reserved, 240.0.0.0/4
+                || a >= 240
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || ip.is_multicast()
+                    // Site-local, deprecated but still routed on some networks
+                    || first & 0xffc0 == 0xfec0)
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 one reaches: IPv4-mapped, NAT64 (64:ff9b::/96) and 6to4
+/// (2002::/16) addresses carry one.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let octets = ip.octets();
+    match ip.segments() {
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(Ipv4Addr::new(
+            octets[12], octets[13], octets[14], octets[15],
+        )),
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => ip.to_ipv4_mapped(),
+    }
+}
+
+/// Resolves names as usual, leaving out private addresses.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|address| is_public(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(NOT_PUBLIC.into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)?
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn is_stream(headers: &HeaderMap) -> bool {
+    let audio = header(headers, CONTENT_TYPE.as_str()).is_some_and(|content_type| {
+        content_type.starts_with("audio/") || content_type.starts_with("application/ogg")
+    });
+    audio || headers.contains_key("icy-metaint") || headers.contains_key("icy-name")
+}
+
+/// Lazily opened direct stream, playable by songbird.
+pub struct Radio {
+    client: reqwest::Client,
+    url: String,
+    on_air: OnAir,
+}
+
+impl Radio {
+    pub fn new(client: reqwest::Client, url: String, on_air: OnAir) -> Self {
+        Self {
+            client,
+            url,
+            on_air,
+        }
+    }
+}
+
+#[async_trait]
+impl Compose for Radio {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        Err(AudioStreamError::Unsupported)
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        // Saved playlists may hold streams added before a host turned private
+        check_public(&self.url)
+            .await
+            .map_err(|e| AudioStreamError::Fail(e.into()))?;
+        let response = self
+            .client
+            .get(&self.url)
+            .header("Icy-MetaData", "1")
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AudioStreamError::Fail(Box::new(e)))?;
+
+        let headers = response.headers();
+        let hint = header(headers, CONTENT_TYPE.as_str()).map(|content_type| {
+            let mut hint = Hint::new();
+            hint.mime_type(&content_type);
+            hint
+        });
+        let metaint = header(headers, "icy-metaint")
+            .and_then(|metaint| metaint.parse().ok())
+            .unwrap_or(0);
+
+        let stream = IcyStream {
+            chunks: Box::pin(response.bytes_stream()),
+            parser: IcyParser::new(metaint),
+            pending: Vec::new(),
+            on_air: self.on_air.clone(),
+        };
+        Ok(AudioStream {
+            input: Box::new(AsyncAdapterStream::new(Box::new(stream), BUFFER_LEN)),
+            hint,
+        })
+    }
+
+    fn should_create_async(&self) -> bool {
+        true
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        Ok(AuxMetadata {
+            title: self.on_air.get(),
+            source_url: Some(self.url.clone()),
+            ..AuxMetadata::default()
+        })
+    }
+}
+
+/// Response body with the ICY metadata blocks taken out.
+struct IcyStream<S> {
+    chunks: Pin<Box<S>>,
+    parser: IcyParser,
+    /// Audio bytes parsed but not yet read
+    pending: Vec<u8>,
+    on_air: OnAir,
+}
+
+impl<S, B> AsyncRead for IcyStream<S>
+where
+    S: Stream<Item = reqwest::Result<B>>,
+    B: AsRef<[u8]>,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = &mut *self;
+        while this.pending.is_empty() {
+            match this.chunks.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    if let Some(title) = this.parser.feed(chunk.as_ref(), &mut this.pending) {
+                        tracing::debug!("Stream now playing \"{}\"", title);
+                        this.on_air.set(title);
+                    }
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(IoError::other(e))),
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let len = this.pending.len().min(buf.remaining());
+        buf.put_slice(&this.pending[..len]);
+        this.pending.drain(..len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncSeek for IcyStream<S> {
+    fn start_seek(self: Pin<&mut Self>, _position: SeekFrom) -> std::io::Result<()> {
+        Err(ErrorKind::Unsupported.into())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        Poll::Ready(Err(ErrorKind::Unsupported.into()))
+    }
+}
+
+#[async_trait]
+impl<S, B> AsyncMediaSource for IcyStream<S>
+where
+    S: Stream<Item = reqwest::Result<B>> + Send + Sync,
+    B: AsRef<[u8]>,
+{
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    async fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Splits an ICY stream into audio and metadata. After every `metaint` audio bytes comes
+/// one length byte, then that many times 16 bytes of metadata.
+#[derive(Debug)]
+struct IcyParser {
+    metaint: usize,
+    state: IcyState,
+}
+
+#[derive(Debug)]
+enum IcyState {
+    Audio { remaining: usize },
+    Length,
+    Metadata { remaining: usize, block: Vec<u8> },
+}
+
+impl IcyParser {
+    /// A parser for blocks every `metaint` bytes; 0 means the stream has no metadata.
+    fn new(metaint: usize) -> Self {
+        Self {
+            metaint,
+            state: IcyState::Audio { remaining: metaint },
+        }
+    }
+
+    /// Append the audio in `input` to `audio`, returning the last title it announced.
+    fn feed(&mut self, mut input: &[u8], audio: &mut Vec<u8>) -> Option<String> {
+        if self.metaint == 0 {
+            audio.extend_from_slice(input);
+            return None;
+        }
+
+        let mut title = None;
+        while !input.is_empty() {
+            match self.state {
+                IcyState::Audio { ref mut remaining } => {
+                    let len = (*remaining).min(input.len());
+                    audio.extend_from_slice(&input[..len]);
+                    input = &input[len..];
+                    *remaining -= len;
+                    if *remaining == 0 {
+                        self.state = IcyState::Length;
+                    }
+                }
+                IcyState::Length => {
+                    let len = usize::from(input[0]) * 16;
+                    input = &input[1..];
+                    self.state = match len {
+                        0 => IcyState::Audio {
+                            remaining: self.metaint,
+                        },
+                        _ => IcyState::Metadata {
+                            remaining: len,
+                            block: Vec::with_capacity(len),
+                        },
+                    };
+                }
+                IcyState::Metadata {
+                    ref mut remaining,
+                    ref mut block,
+                } => {
+                    let len = (*remaining).min(input.len());
+                    block.extend_from_slice(&input[..len]);
+                    input = &input[len..];
+                    *remaining -= len;
+                    if *remaining == 0 {
+                        title = stream_title(block).or(title);
+                        self.state = IcyState::Audio {
+                            remaining: self.metaint,
+                        };
+                    }
+                }
+            }
+        }
+        title
+    }
+}
+
+/// `StreamTitle` from a metadata block such as `StreamTitle='Artist - Song';StreamUrl='';`.
+fn stream_title(block: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(block);
+    let text = text.trim_end_matches('\0');
+    let start = text.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &text[start..];
+    // Titles may contain quotes themselves, so the field ends at the next `';`
+    let end = rest
+        .find("';")
+        .unwrap_or_else(|| rest.trim_end_matches('\'').len());
+    let title = rest[..end].trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use rstest::rstest;
+
+    /// An ICY stream with `metaint` 4: audio, a metadata block, more audio, an empty block.
+    fn icy_stream() -> Vec<u8> {
+        let mut metadata = b"StreamTitle='Daft Punk - Aerodynamic';".to_vec();
+        metadata.resize(48, 0);
+        let mut stream = b"abcd".to_vec();
+        stream.push(3);
+        stream.extend(metadata);
+        stream.extend(b"efgh");
+        stream.push(0);
+        stream.extend(b"ij");
+        stream
+    }
+
+    #[test]
+    fn test_parser_strips_metadata() {
+        let mut parser = IcyParser::new(4);
+        let mut audio = Vec::new();
+        let title = parser.feed(&icy_stream(), &mut audio);
+
+        assert_eq!(audio, b"abcdefghij");
+        assert_eq!(title.as_deref(), Some("Daft Punk - Aerodynamic"));
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(3)]
+    #[case(7)]
+    fn test_parser_across_chunks(#[case] chunk_len: usize) {
+        let mut parser = IcyParser::new(4);
+        let mut audio = Vec::new();
+        let mut titles = Vec::new();
+        for chunk in icy_stream().chunks(chunk_len) {
+            titles.extend(parser.feed(chunk, &mut audio));
+        }
+
+        assert_eq!(audio, b"abcdefghij");
+        assert_eq!(titles, vec!["Daft Punk - Aerodynamic".to_string()]);
+    }
+
+    #[test]
+    fn test_parser_without_metadata() {
+        let mut parser = IcyParser::new(0);
+        let mut audio = Vec::new();
+        assert_eq!(parser.feed(b"abc\x03def", &mut audio), None);
+        assert_eq!(audio, b"abc\x03def");
+    }
+
+    #[rstest]
+    #[case(
+        b"StreamTitle='Air - Sexy Boy';StreamUrl='';\0\0",
+        Some("Air - Sexy Boy")
+    )]
+    #[case(
+        b"StreamTitle='Guns N' Roses - Patience';",
+        Some("Guns N' Roses - Patience")
+    )]
+    #[case(b"StreamTitle='';\0\0\0", None)]
+    #[case(b"StreamUrl='https://example.com';", None)]
+    fn test_stream_title(#[case] block: &[u8], #[case] expected: Option<&str>) {
+        assert_eq!(stream_title(block).as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case("http://radio.example.com:8000/live.mp3", true)]
+    #[case("https://example.com/Song.OGG?token=1", true)]
+    #[case("http://radio.example.com:8000/stream", false)]
+    #[case("https://www.youtube.com/watch?v=abc", false)]
+    #[case("https://soundcloud.com/artist/track", false)]
+    #[case("https://example.com/mp3/", false)]
+    fn test_looks_like_stream(#[case] url: &str, #[case] expected: bool) {
+        assert_eq!(looks_like_stream(url), expected);
+    }
+
+    #[rstest]
+    #[case("http://radio.example.com/live.mp3", true)]
+    #[case("http://93.184.216.34/live.mp3", true)]
+    #[case("http://[2606:4700::1111]/live.mp3", true)]
+    #[case("http://localhost:8000/live.mp3", false)]
+    #[case("http://api.LOCALHOST./live.mp3", false)]
+    #[case("http://127.0.0.1/live.mp3", false)]
+    #[case("http://10.1.2.3/live.mp3", false)]
+    #[case("http://192.168.0.10/live.mp3", false)]
+    #[case("http://169.254.169.254/latest/meta-data", false)]
+    #[case("http://100.64.0.1/live.mp3", false)]
+    #[case("http://0.0.0.0/live.mp3", false)]
+    #[case("http://[::1]/live.mp3", false)]
+    #[case("http://[fd00::1]/live.mp3", false)]
+    #[case("http://[fe80::1]/live.mp3", false)]
+    #[case("http://[::ffff:127.0.0.1]/live.mp3", false)]
+    #[case("http://224.0.0.1/live.mp3", false)]
+    #[case("http://239.255.255.250/live.mp3", false)]
+    #[case("http://240.0.0.1/live.mp3", false)]
+    #[case("http://[ff02::1]/live.mp3", false)]
+    #[case("http://[ff0e::1]/live.mp3", false)]
+    #[case("http://[64:ff9b::7f00:1]/live.mp3", false)]
+    #[case("http://[64:ff9b::a01:203]/live.mp3", false)]
+    #[case("http://[64:ff9b::5db8:d822]/live.mp3", true)]
+    #[case("http://[2002:7f00:1::1]/live.mp3", false)]
+    #[case("http://[2002:c0a8:a::1]/live.mp3", false)]
+    #[case("http://[2002:5db8:d822::1]/live.mp3", true)]
+    fn test_is_public_host(#[case] url: &str, #[case] expected: bool) {
+        assert_eq!(is_public_host(&Url::parse(url).unwrap()), expected);
+    }
+
+    #[tokio::test]
+    async fn test_check_public_refuses_literals() {
+        assert_eq!(
+            check_public("http://127.0.0.1:8000/live.mp3").await,
+            Err(NOT_PUBLIC.to_string())
+        );
+        assert!(check_public("not a link").await.is_err());
+    }
+
+    #[rstest]
+    #[case(&[("content-type", "audio/mpeg")], true)]
+    #[case(&[("content-type", "application/ogg")], true)]
+    #[case(&[("content-type", "text/html"), ("icy-name", "Radio")], true)]
+    #[case(&[("content-type", "text/html; charset=utf-8")], false)]
+    #[case(&[], false)]
+    fn test_is_stream(#[case] headers: &[(&'static str, &'static str)], #[case] expected: bool) {
+        let headers: HeaderMap = headers
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect();
+        assert_eq!(is_stream(&headers), expected);
+    }
+}
@@ -0,0 +1,162 @@
+//! SoundCloud links. yt-dlp plays single tracks on its own, finding a client id and
+//! picking an HLS or progressive stream, but only the first track of a set, so sets
+//! are listed here and queued track by track.
+
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::player::ytdlp::Ytdlp;
+
+const HOSTS: [&str; 2] = ["soundcloud.com", "m.soundcloud.com"];
+
+/// A track listed in a set. Listing skips extraction, so only tracks SoundCloud sent in
+/// full come with a title and length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetTrack {
+    pub url: String,
+    pub title: String,
+    pub duration: Option<Duration>,
+}
+
+#[derive(Deserialize)]
+struct Set {
+    #[serde(default)]
+    entries: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    url: Option<String>,
+    title: Option<String>,
+    duration: Option<f64>,
+}
+
+/// Whether `url` is a SoundCloud set (playlist or album), `soundcloud.com/<user>/sets/<name>`.
+pub fn is_set(url: &str) -> bool {
+    let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    else {
+        return false;
+    };
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    matches!(segments.as_slice(), [host, _, "sets", _] if HOSTS.contains(host))
+}
+
+/// The first `limit` tracks of a set.
+pub async fn set_tracks(ytdlp: &Ytdlp, url: &str, limit: usize) -> Result<Vec<SetTrack>, String> {
+    let output = ytdlp
+        .command()
+        .args(["-J", "--flat-playlist", "--no-warnings", "--playlist-end"])
+        .arg(limit.to_string())
+        .arg("--")
+        .arg(url)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Could not run yt-dlp: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Could not load <{url}>: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let set: Set = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Unexpected yt-dlp output for <{url}>: {e}"))?;
+    Ok(parse_set(set, limit))
+}
+
+fn parse_set(set: Set, limit: usize) -> Vec<SetTrack> {
+    set.entries
+        .into_iter()
+        .filter_map(|entry| {
+            let url = entry.url?;
+            Some(SetTrack {
+                title: entry.title.unwrap_or_else(|| title_from_url(&url)),
+                duration: entry
+                    .duration
+                    .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                    .map(Duration::from_secs_f64),
+                url,
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+/// Readable stand-in for a missing title, from a `soundcloud.com/<user>/<track>` permalink.
+fn title_from_url(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match segments.as_slice() {
+        [.., host, user, track] if HOSTS.contains(host) => {
+            format!("{} - {}", user.replace('-', " "), track.replace('-', " "))
+        }
+        [.., "tracks", id] => format!("SoundCloud track {id}"),
+        _ => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("https://soundcloud.com/daftpunk/sets/discovery", true)]
+    #[case("https://m.soundcloud.com/daftpunk/sets/discovery?si=abc", true)]
+    #[case("https://soundcloud.com/daftpunk/one-more-time", false)]
+    #[case("https://soundcloud.com/daftpunk", false)]
+    #[case("https://example.com/daftpunk/sets/discovery", false)]
+    fn test_is_set(#[case] url: &str, #[case] expected: bool) {
+        assert_eq!(is_set(url), expected);
+    }
+
+    #[rstest]
+    #[case(
+        "https://soundcloud.com/daft-punk/one-more-time",
+        "daft punk - one more time"
+    )]
+    #[case(
+        "https://api-v2.soundcloud.com/tracks/12345?secret_token=s-abc",
+        "SoundCloud track 12345"
+    )]
+    fn test_title_from_url(#[case] url: &str, #[case] expected: &str) {
+        assert_eq!(title_from_url(url), expected);
+    }
+
+    #[test]
+    fn test_parse_set() {
+        let set: Set = serde_json::from_str(
+            r#"{
+                "title": "Discovery",
+                "entries": [
+                    {"url": "https://soundcloud.com/daftpunk/one-more-time",
+                     "title": "One More Time", "duration": 320.4},
+                    {"url": "https://api-v2.soundcloud.com/tracks/2"},
+                    {"title": "No url"},
+                    {"url": "https://soundcloud.com/daftpunk/digital-love"}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            parse_set(set, 2),
+            vec![
+                SetTrack {
+                    url: "https://soundcloud.com/daftpunk/one-more-time".to_string(),
+                    title: "One More Time".to_string(),
+                    duration: Some(Duration::from_secs_f64(320.4)),
+                },
+                SetTrack {
+                    url: "https://api-v2.soundcloud.com/tracks/2".to_string(),
+                    title: "SoundCloud track 2".to_string(),
+                    duration: None,
+                },
+            ]
+        );
+    }
+}
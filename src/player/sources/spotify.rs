@@ -0,0 +1,353 @@
+//! Spotify links, looked up through the Spotify Web API and played from YouTube.
+//!
+//! Spotify streams can't be played by third parties, so each track becomes a yt-dlp
+//! `ytsearch1:` query for its artists and title. The search runs when the track starts,
+//! which keeps queueing a long playlist fast.
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::config::SpotifyConfig;
+
+const API_URL: &str = "https://api.spotify.com/v1";
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const HOST: &str = "open.spotify.com";
+/// Tokens are renewed this long before Spotify expires them.
+const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Track,
+    Album,
+    Playlist,
+}
+
+/// A Spotify track, album or playlist link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub kind: Kind,
+    pub id: String,
+}
+
+impl Link {
+    /// Parse `https://open.spotify.com/[intl-xx/]<kind>/<id>`, ignoring any query string.
+    pub fn parse(url: &str) -> Option<Self> {
+        let path = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))?
+            .strip_prefix(HOST)?
+            .split(['?', '#'])
+            .next()?;
+        let mut segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .skip_while(|segment| segment.starts_with("intl-"));
+        let kind = match segments.next()? {
+            "track" => Kind::Track,
+            "album" => Kind::Album,
+            "playlist" => Kind::Playlist,
+            _ => return None,
+        };
+        let id = segments.next()?;
+        if !id.chars().all(|c| c.is_ascii_alphanumeric()) || segments.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            kind,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// Whether `url` points at Spotify at all, including links `Link` doesn't understand.
+pub fn is_spotify(url: &str) -> bool {
+    url.strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .and_then(|rest| rest.split(['/', '?', '#']).next())
+        .is_some_and(|host| host == HOST)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Track {
+    pub name: String,
+    /// Missing for podcast episodes in playlists
+    #[serde(default)]
+    artists: Vec<Artist>,
+    duration_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Artist {
+    name: String,
+}
+
+impl Track {
+    /// `Artist, Artist - Name`, or just the name when there are no artists.
+    pub fn title(&self) -> String {
+        if self.artists.is_empty() {
+            return self.name.clone();
+        }
+        let artists: Vec<&str> = self
+            .artists
+            .iter()
+            .map(|artist| artist.name.as_str())
+            .collect();
+        format!("{} - {}", artists.join(", "), self.name)
+    }
+
+    /// yt-dlp URL that plays the best YouTube match.
+    pub fn search_url(&self) -> String {
+        format!("ytsearch1:{}", self.title())
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PlaylistItem {
+    /// Null for tracks that were removed from Spotify
+    track: Option<Track>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+struct Token {
+    value: String,
+    expires: Instant,
+}
+
+/// Spotify Web API client using the client credentials flow, which can read public
+/// catalog data and playlists but nothing private to a user.
+pub struct Spotify {
+    client: reqwest::Client,
+    client_id: String,
+    client_secret: String,
+    max_tracks: usize,
+    token: AsyncMutex<Option<Token>>,
+}
+
+impl Spotify {
+    /// A client, if both credentials are configured.
+    pub fn new(config: &SpotifyConfig) -> Option<Self> {
+        Some(Self {
+            client: reqwest::Client::new(),
+            client_id: config.client_id.clone()?,
+            client_secret: config.client_secret.clone()?,
+            max_tracks: config.max_tracks,
+            token: AsyncMutex::new(None),
+        })
+    }
+
+    /// Tracks behind a link, at most `max_tracks` of them for albums and playlists.
+    pub async fn tracks(&self, link: &Link) -> Result<Vec<Track>, String> {
+        let id = &link.id;
+        match link.kind {
+            Kind::Track => Ok(vec![self.get(&format!("{API_URL}/tracks/{id}")).await?]),
+            Kind::Album => {
+                self.pages(format!("{API_URL}/albums/{id}/tracks?limit=50"), Some)
+                    .await
+            }
+            Kind::Playlist => {
+                self.pages(
+                    format!("{API_URL}/playlists/{id}/tracks?limit=100"),
+                    |item: PlaylistItem| item.track,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Follow a paged listing until it ends or `max_tracks` are collected.
+    async fn pages<T: DeserializeOwned>(
+        &self,
+        url: String,
+        track: impl Fn(T) -> Option<Track>,
+    ) -> Result<Vec<Track>, String> {
+        let mut tracks = Vec::new();
+        let mut next = Some(url);
+        while let Some(url) = next.take()
+            && tracks.len() < self.max_tracks
+        {
+            let page: Page<T> = self.get(&url).await?;
+            tracks.extend(page.items.into_iter().filter_map(&track));
+            next = page.next;
+        }
+        tracks.truncate(self.max_tracks);
+        Ok(tracks)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T, String> {
+        let token = self.token().await?;
+        let response = self
+            .client
+            .get(url)
+            .bearer_auth(token)
+            .send()
+            .await
+            .map_err(|e| format!("Could not reach Spotify: {e}"))?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(
+                "Spotify has nothing public at that link; private playlists can't be played."
+                    .to_string(),
+            ),
+            StatusCode::UNAUTHORIZED => {
+                *self.token.lock().await = None;
+                Err("Spotify rejected the access token, please try again.".to_string())
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                Err("Spotify is rate limiting requests, please try again later.".to_string())
+            }
+            status if !status.is_success() => Err(format!("Spotify returned {status}")),
+            _ => response
+                .json()
+                .await
+                .map_err(|e| format!("Unexpected response from Spotify: {e}")),
+        }
+    }
+
+    /// Current access token, requesting a new one when it is about to expire.
+    async fn token(&self) -> Result<String, String> {
+        let mut token = self.token.lock().await;
+        if let Some(ref current) = *token
+            && current.expires > Instant::now() + TOKEN_MARGIN
+        {
+            return Ok(current.value.clone());
+        }
+
+        let response: TokenResponse = self
+            .client
+            .post(TOKEN_URL)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Could not authenticate with Spotify: {e}"))?
+            .json()
+            .await
+            .map_err(|e| format!("Unexpected token response from Spotify: {e}"))?;
+
+        let value = response.access_token;
+        *token = Some(Token {
+            value: value.clone(),
+            expires: Instant::now() + Duration::from_secs(response.expires_in),
+        });
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        "https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC",
+        Kind::Track,
+        "4uLU6hMCjMI75M1A2tKUQC"
+    )]
+    #[case(
+        "https://open.spotify.com/album/1DFixLWuPkv3KT3TnV35m3?si=abc",
+        Kind::Album,
+        "1DFixLWuPkv3KT3TnV35m3"
+    )]
+    #[case(
+        "https://open.spotify.com/intl-de/playlist/37i9dQZF1DXcBWIGoYBM5M",
+        Kind::Playlist,
+        "37i9dQZF1DXcBWIGoYBM5M"
+    )]
+    fn test_parse_link(#[case] url: &str, #[case] kind: Kind, #[case] id: &str) {
+        assert_eq!(
+            Link::parse(url),
+            Some(Link {
+                kind,
+                id: id.to_string()
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("https://open.spotify.com/artist/0OdUWJ0sBjDrqHygGUXeCF")]
+    #[case("https://open.spotify.com/track/")]
+    #[case("https://open.spotify.com/track/abc/extra")]
+    #[case("https://open.spotify.com.example.com/track/abc")]
+    #[case("https://www.youtube.com/watch?v=abc")]
+    fn test_parse_link_invalid(#[case] url: &str) {
+        assert_eq!(Link::parse(url), None);
+    }
+
+    #[rstest]
+    #[case("https://open.spotify.com/artist/0OdUWJ0sBjDrqHygGUXeCF", true)]
+    #[case("http://open.spotify.com/track/abc", true)]
+    #[case("https://open.spotify.com.example.com/track/abc", false)]
+    #[case("https://www.youtube.com/watch?v=abc", false)]
+    fn test_is_spotify(#[case] url: &str, #[case] expected: bool) {
+        assert_eq!(is_spotify(url), expected);
+    }
+
+    #[test]
+    fn test_track_from_playlist_page() {
+        let page: Page<PlaylistItem> = serde_json::from_str(
+            r#"{
+                "items": [
+                    {"track": {"name": "One More Time", "duration_ms": 320357,
+                               "artists": [{"name": "Daft Punk"}]}},
+                    {"track": null},
+                    {"track": {"name": "Episode 12", "duration_ms": 60000}}
+                ],
+                "next": null
+            }"#,
+        )
+        .unwrap();
+        let tracks: Vec<Track> = page
+            .items
+            .into_iter()
+            .filter_map(|item| item.track)
+            .collect();
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].title(), "Daft Punk - One More Time");
+        assert_eq!(
+            tracks[0].search_url(),
+            "ytsearch1:Daft Punk - One More Time"
+        );
+        assert_eq!(tracks[0].duration(), Duration::from_millis(320357));
+        assert_eq!(tracks[1].title(), "Episode 12");
+    }
+
+    #[test]
+    fn test_track_title_with_several_artists() {
+        let track: Track = serde_json::from_str(
+            r#"{"name": "Get Lucky", "duration_ms": 1,
+                "artists": [{"name": "Daft Punk"}, {"name": "Pharrell Williams"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(track.title(), "Daft Punk, Pharrell Williams - Get Lucky");
+    }
+
+    #[test]
+    fn test_new_needs_both_credentials() {
+        let config = SpotifyConfig {
+            client_id: Some("id".to_string()),
+            ..SpotifyConfig::default()
+        };
+        assert!(Spotify::new(&config).is_none());
+    }
+}
@@ -0,0 +1,320 @@
+//! Running yt-dlp with the configured binary, cookies, proxy and extra arguments.
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use serenity::async_trait;
+use songbird::input::core::io::MediaSource;
+use songbird::input::{
+    AudioStream, AudioStreamError, AuxMetadata, Compose, HlsRequest, HttpRequest, Input,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::config::YtdlpConfig;
+use crate::player::sources::radio;
+
+/// Format picked for playback: the best audio-only stream, or the best of any.
+const FORMAT: &str = "ba[abr>0][vcodec=none]/best";
+
+#[derive(Debug, Clone)]
+pub struct Ytdlp {
+    program: &'static str,
+    args: Vec<String>,
+    /// Fetches the extracted streams, through the same proxy as yt-dlp so stream URLs
+    /// bound to the extracting address keep working
+    http_client: reqwest::Client,
+    /// Fetches direct streams users link to, through the same proxy but only from public
+    /// addresses
+    stream_client: reqwest::Client,
+}
+
+impl Ytdlp {
+    pub fn new(config: &YtdlpConfig) -> Result<Self, String> {
+        let http_client = client_builder(config)?
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client for yt-dlp streams: {e}"))?;
+        let stream_client = radio::public_only(client_builder(config)?)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client for direct streams: {e}"))?;
+
+        Ok(Self {
+            // songbird needs a 'static program name; this is created once at startup
+            program: Box::leak(config.path.clone().into_boxed_str()),
+            args: args(config),
+            http_client,
+            stream_client,
+        })
+    }
+
+    /// Lazily extracted audio source for `url`.
+    pub fn source(&self, url: &str) -> Extraction {
+        Extraction {
+            ytdlp: self.clone(),
+            url: url.to_string(),
+            metadata: None,
+        }
+    }
+
+    /// yt-dlp's pick of stream for `url`. The URL goes after `--`, so text from users is
+    /// never read as an option. The process is killed if the returned future is
+    /// dropped, e.g. by a timeout.
+    async fn extract(&self, url: &str) -> Result<Extracted, String> {
+        let output = self
+            .command()
+            .args(["-j", "-f", FORMAT, "--no-playlist", "--no-warnings", "--"])
+            .arg(url)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| format!("Could not run yt-dlp: {e}"))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "yt-dlp failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let line = output
+            .stdout
+            .split(|&byte| byte == b'\n')
+            .find(|line| !line.is_empty())
+            .ok_or("yt-dlp found nothing to play")?;
+        serde_json::from_slice(line).map_err(|e| format!("Unexpected yt-dlp output: {e}"))
+    }
+
+    /// Client for direct streams, going through the same proxy as yt-dlp.
+    pub fn stream_client(&self) -> &reqwest::Client {
+        &self.stream_client
+    }
+
+    /// Version reported by the yt-dlp binary.
+    pub async fn version(&self) -> Result<String, String> {
+        let output = self
+            .command()
+            .arg("--version")
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| format!("Could not run yt-dlp: {e}"))?;
+        if !output.status.success() {
+            return Err(format!("yt-dlp exited with {}", output.status));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// A yt-dlp invocation with the configured arguments, ready for more.
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(self.program);
+        command.args(&self.args);
+        command
+    }
+}
+
+/// HTTP client settings shared by yt-dlp's streams and direct ones.
+fn client_builder(config: &YtdlpConfig) -> Result<reqwest::ClientBuilder, String> {
+    let builder = reqwest::Client::builder();
+    match config.proxy {
+        Some(ref proxy) => {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| format!("Invalid ytdlp.proxy `{proxy}`: {e}"))?;
+            Ok(builder.proxy(proxy))
+        }
+        None => Ok(builder),
+    }
+}
+
+/// Command line arguments derived from the configuration, passed before any others.
+fn args(config: &YtdlpConfig) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(ref cookies) = config.cookies {
+        args.push("--cookies".to_string());
+        args.push(cookies.display().to_string());
+    }
+    if let Some(ref proxy) = config.proxy {
+        args.push("--proxy".to_string());
+        args.push(proxy.clone());
+    }
+    args.extend(config.args.iter().cloned());
+    args
+}
+
+/// The stream yt-dlp picked for a URL and how to fetch it.
+#[derive(Debug, Deserialize)]
+struct Extracted {
+    url: String,
+    title: Option<String>,
+    duration: Option<f64>,
+    filesize: Option<u64>,
+    http_headers: Option<HashMap<String, String>>,
+    protocol: Option<String>,
+    webpage_url: Option<String>,
+}
+
+impl Extracted {
+    fn metadata(&self) -> AuxMetadata {
+        AuxMetadata {
+            title: self.title.clone(),
+            duration: self
+                .duration
+                .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                .map(Duration::from_secs_f64),
+            source_url: self.webpage_url.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A track played through yt-dlp, extracted when it starts.
+pub struct Extraction {
+    ytdlp: Ytdlp,
+    url: String,
+    metadata: Option<AuxMetadata>,
+}
+
+impl From<Extraction> for Input {
+    fn from(extraction: Extraction) -> Self {
+        Input::Lazy(Box::new(extraction))
+    }
+}
+
+#[async_trait]
+impl Compose for Extraction {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        Err(AudioStreamError::Unsupported)
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let extracted = self
+            .ytdlp
+            .extract(&self.url)
+            .await
+            .map_err(|e| AudioStreamError::Fail(e.into()))?;
+        self.metadata = Some(extracted.metadata());
+
+        let headers: HeaderMap = extracted
+            .http_headers
+            .iter()
+            .flatten()
+            .filter_map(|(name, value)| {
+                Some((
+                    HeaderName::from_bytes(name.as_bytes()).ok()?,
+                    HeaderValue::from_str(value).ok()?,
+                ))
+            })
+            .collect();
+        let client = self.ytdlp.http_client.clone();
+        match extracted.protocol.as_deref() {
+            Some("m3u8_native") => {
+                HlsRequest::new_with_headers(client, extracted.url, headers).create()
+            }
+            _ => {
+                HttpRequest {
+                    client,
+                    request: extracted.url,
+                    headers,
+                    content_length: extracted.filesize,
+                }
+                .create_async()
+                .await
+            }
+        }
+    }
+
+    fn should_create_async(&self) -> bool {
+        true
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        if let Some(metadata) = &self.metadata {
+            return Ok(metadata.clone());
+        }
+        let metadata = self
+            .ytdlp
+            .extract(&self.url)
+            .await
+            .map_err(|e| AudioStreamError::Fail(e.into()))?
+            .metadata();
+        self.metadata = Some(metadata.clone());
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_args_default() {
+        assert!(args(&YtdlpConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_args_full() {
+        let config = YtdlpConfig {
+            path: "/opt/yt-dlp".to_string(),
+            cookies: Some(PathBuf::from("/etc/triboferrin/cookies.txt")),
+            proxy: Some("socks5://127.0.0.1:1080".to_string()),
+            args: vec!["--force-ipv4".to_string()],
+        };
+        assert_eq!(
+            args(&config),
+            vec![
+                "--cookies",
+                "/etc/triboferrin/cookies.txt",
+                "--proxy",
+                "socks5://127.0.0.1:1080",
+                "--force-ipv4",
+            ]
+        );
+    }
+
+    /// A yt-dlp stand-in that reports the arguments it was given as the title.
+    #[cfg(unix)]
+    fn fake_ytdlp(name: &str) -> (PathBuf, Ytdlp) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("triboferrin_fake_ytdlp_{name}"));
+        std::fs::write(
+            &path,
+            "#!/bin/sh\nprintf '{\"url\": \"http://127.0.0.1/a\", \"title\": \"%s\"}\\n' \"$*\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = YtdlpConfig {
+            path: path.display().to_string(),
+            ..Default::default()
+        };
+        (path, Ytdlp::new(&config).unwrap())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_url_passed_after_options() {
+        let (path, ytdlp) = fake_ytdlp("url_after_options");
+        let metadata = ytdlp
+            .source("--exec=touch /tmp/owned")
+            .aux_metadata()
+            .await
+            .unwrap();
+        assert!(
+            metadata
+                .title
+                .unwrap()
+                .ends_with(" -- --exec=touch /tmp/owned")
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_proxy() {
+        let config = YtdlpConfig {
+            proxy: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(Ytdlp::new(&config).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! The bot's "Listening to" status, kept in step with playback events.
+//!
+//! A bot in a single server shows the track playing there. Once it plays in several it
+//! only shows how many, so one server can't see what another is listening to.
+
+use serenity::all::{ActivityData, GuildId, ShardManager};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::events::Event;
+
+/// Discord cuts activity names at 128 characters.
+const MAX_LEN: usize = 128;
+
+/// Update every shard's status as tracks start and stop.
+pub fn spawn(shard_manager: Arc<ShardManager>, mut events: Receiver<Event>) {
+    tokio::spawn(async move {
+        let mut playing = Playing::default();
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::debug!("Presence missed {} playback events", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            if !playing.apply(&event) {
+                continue;
+            }
+            let activity = playing.status().map(ActivityData::listening);
+            for runner in shard_manager.runners.lock().await.values() {
+                runner.runner_tx.set_activity(activity.clone());
+            }
+        }
+    });
+}
+
+/// Title playing in each guild.
+#[derive(Debug, Default)]
+struct Playing {
+    guilds: HashMap<GuildId, String>,
+}
+
+impl Playing {
+    /// Track `event`, returning whether the status may have changed.
+    fn apply(&mut self, event: &Event) -> bool {
+        match event {
+            Event::TrackStarted { guild_id, track } => {
+                self.guilds.insert(*guild_id, track.title.clone());
+                true
+            }
+            Event::PlaybackStopped { guild_id } => self.guilds.remove(guild_id).is_some(),
+            Event::VoiceConnected { .. } | Event::QueueChanged { .. } => false,
+        }
+    }
+
+    fn status(&self) -> Option<String> {
+        let status = match self.guilds.len() {
+            0 => return None,
+            1 => self.guilds.values().next()?.clone(),
+            servers => format!("music in {servers} servers"),
+        };
+        if status.chars().count() > MAX_LEN {
+            let cut: String = status.chars().take(MAX_LEN - 1).collect();
+            return Some(format!("{cut}…"));
+        }
+        Some(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::queue::{QueuedTrack, Source};
+    use serenity::all::UserId;
+
+    fn started(guild: u64, title: &str) -> Event {
+        Event::TrackStarted {
+            guild_id: GuildId::new(guild),
+            track: QueuedTrack {
+                url: format!("https://example.com/{title}"),
+                title: title.to_string(),
+                duration: None,
+                requester: UserId::new(1),
+                source: Source::Ytdlp,
+            },
+        }
+    }
+
+    #[test]
+    fn test_status_follows_playback() {
+        let mut playing = Playing::default();
+        assert_eq!(playing.status(), None);
+
+        assert!(playing.apply(&started(1, "Song")));
+        assert_eq!(playing.status().as_deref(), Some("Song"));
+
+        assert!(playing.apply(&started(2, "Other")));
+        assert_eq!(playing.status().as_deref(), Some("music in 2 servers"));
+
+        assert!(playing.apply(&Event::PlaybackStopped {
+            guild_id: GuildId::new(1)
+        }));
+        assert_eq!(playing.status().as_deref(), Some("Other"));
+
+        assert!(!playing.apply(&Event::PlaybackStopped {
+            guild_id: GuildId::new(1)
+        }));
+        assert!(!playing.apply(&Event::QueueChanged {
+            guild_id: GuildId::new(2),
+            length: 3
+        }));
+    }
+
+    #[test]
+    fn test_long_title_is_cut() {
+        let mut playing = Playing::default();
+        playing.apply(&started(1, &"a".repeat(200)));
+        assert_eq!(playing.status().unwrap().chars().count(), MAX_LEN);
+    }
+}
@@ -0,0 +1,93 @@
+use serenity::http::{Http, HttpBuilder};
+use serenity_reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::time::Duration;
+
+use crate::config::{Config, ProxyConfig};
+
+/// Build the HTTP client used for the Discord API requests sent through a proxy.
+fn proxy_client(proxy: &ProxyConfig) -> Result<serenity_reqwest::Client, String> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &proxy.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| format!("Invalid proxy header name `{name}`: {e}"))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| format!("Invalid value for proxy header `{name}`: {e}"))?;
+        headers.insert(name, value);
+    }
+
+    let mut builder = serenity_reqwest::Client::builder()
+        .use_rustls_tls()
+        .default_headers(headers);
+    if let Some(timeout) = proxy.timeout {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Build the Discord HTTP client, routing requests through `discord_api_url` when set.
+pub fn build_http(config: &Config) -> Result<Http, String> {
+    let builder = HttpBuilder::new(&config.discord_token);
+
+    let Some(ref api_url) = config.discord_api_url else {
+        return Ok(builder.build());
+    };
+
+    let proxy = &config.discord.proxy;
+    tracing::info!(
+        "Using custom Discord API URL: {} (ratelimiter {})",
+        api_url,
+        if proxy.ratelimiter {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+
+    Ok(builder
+        .proxy(api_url)
+        .ratelimiter_disabled(!proxy.ratelimiter)
+        .client(proxy_client(proxy)?)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_proxy_client_defaults() {
+        assert!(proxy_client(&ProxyConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn test_proxy_client_with_headers_and_timeout() {
+        let proxy = ProxyConfig {
+            timeout: Some(5),
+            headers: BTreeMap::from([("X-Proxy-Auth".to_string(), "secret".to_string())]),
+            ..Default::default()
+        };
+        assert!(proxy_client(&proxy).is_ok());
+    }
+
+    #[test]
+    fn test_proxy_client_invalid_header_name() {
+        let proxy = ProxyConfig {
+            headers: BTreeMap::from([("Bad Header".to_string(), "value".to_string())]),
+            ..Default::default()
+        };
+        let error = proxy_client(&proxy).unwrap_err();
+        assert!(error.starts_with("Invalid proxy header name `Bad Header`"));
+    }
+
+    #[test]
+    fn test_proxy_client_invalid_header_value() {
+        let proxy = ProxyConfig {
+            headers: BTreeMap::from([("X-Proxy-Auth".to_string(), "line\nbreak".to_string())]),
+            ..Default::default()
+        };
+        let error = proxy_client(&proxy).unwrap_err();
+        assert!(error.starts_with("Invalid value for proxy header `x-proxy-auth`"));
+    }
+}
@@ -0,0 +1,227 @@
+use std::path::PathBuf;
+
+/// How the service runs the bot: the binary, the directory relative paths in the config
+/// resolve against, and the config file and profile it was generated with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub program: PathBuf,
+    pub working_dir: PathBuf,
+    pub config: Option<PathBuf>,
+    pub profile: Option<String>,
+}
+
+impl Invocation {
+    /// Command line arguments after the program.
+    fn arguments(&self) -> Vec<String> {
+        let mut arguments = Vec::new();
+        if let Some(config) = &self.config {
+            arguments.push("--config".to_string());
+            arguments.push(config.display().to_string());
+        }
+        if let Some(profile) = &self.profile {
+            arguments.push("--profile".to_string());
+            arguments.push(profile.clone());
+        }
+        arguments
+    }
+}
+
+/// Escape text for inclusion in an XML document.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Quote an argument for a Windows command line, as the C runtime parses it.
+fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    // Backslashes before the closing quote would escape it
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+/// Render a launchd property list that keeps the bot running on macOS.
+/// The token is intentionally not embedded; supply it via the config file.
+pub fn launchd_plist(label: &str, invocation: &Invocation) -> String {
+    let arguments: String = std::iter::once(invocation.program.display().to_string())
+        .chain(invocation.arguments())
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(&arg)))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>WorkingDirectory</key>
+    <string>{working_dir}</string>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>StandardOutPath</key>
+    <string>/tmp/{label}.log</string>
+    <key>StandardErrorPath</key>
+    <string>/tmp/{label}.log</string>
+</dict>
+</plist>
+"#,
+        label = xml_escape(label),
+        working_dir = xml_escape(&invocation.working_dir.display().to_string()),
+    )
+}
+
+/// Render a Windows Task Scheduler definition that starts the bot at boot as the Local
+/// Service account and restarts it when it fails. This stands in for a Windows service,
+/// which the bot doesn't implement: it has no Service Control Manager handler.
+/// The token is intentionally not embedded; supply it via the config file.
+pub fn scheduled_task(name: &str, invocation: &Invocation) -> String {
+    let arguments = invocation
+        .arguments()
+        .iter()
+        .map(|arg| windows_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        r#"<?xml version="1.0"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <URI>\{name}</URI>
+    <Description>triboferrin Discord bot</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <BootTrigger>
+      <Enabled>true</Enabled>
+    </BootTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <UserId>S-1-5-19</UserId>
+      <RunLevel>LeastPrivilege</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <StartWhenAvailable>true</StartWhenAvailable>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+    <RestartOnFailure>
+      <Interval>PT1M</Interval>
+      <Count>999</Count>
+    </RestartOnFailure>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{program}</Command>
+      <Arguments>{arguments}</Arguments>
+      <WorkingDirectory>{working_dir}</WorkingDirectory>
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        name = xml_escape(name),
+        program = xml_escape(&invocation.program.display().to_string()),
+        arguments = xml_escape(&arguments),
+        working_dir = xml_escape(&invocation.working_dir.display().to_string()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn invocation(config: Option<&str>, profile: Option<&str>) -> Invocation {
+        Invocation {
+            program: PathBuf::from("/usr/local/bin/triboferrin"),
+            working_dir: PathBuf::from("/var/lib/triboferrin"),
+            config: config.map(PathBuf::from),
+            profile: profile.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_launchd_plist_without_config() {
+        let plist = launchd_plist("triboferrin", &invocation(None, None));
+
+        assert!(plist.contains("<string>triboferrin</string>"));
+        assert!(plist.contains("<string>/usr/local/bin/triboferrin</string>\n    </array>"));
+        assert!(!plist.contains("--config"));
+        assert!(
+            plist
+                .contains("<key>WorkingDirectory</key>\n    <string>/var/lib/triboferrin</string>")
+        );
+    }
+
+    #[test]
+    fn test_launchd_plist_with_config() {
+        let plist = launchd_plist(
+            "triboferrin",
+            &invocation(Some("/etc/triboferrin & co/config.toml"), Some("prod")),
+        );
+
+        assert!(plist.contains(
+            "        <string>--config</string>\n        <string>/etc/triboferrin &amp; co/config.toml</string>\n"
+        ));
+        assert!(plist.contains(
+            "        <string>--profile</string>\n        <string>prod</string>\n    </array>"
+        ));
+    }
+
+    #[test]
+    fn test_scheduled_task() {
+        let invocation = Invocation {
+            program: PathBuf::from(r"C:\Program Files\triboferrin\triboferrin.exe"),
+            working_dir: PathBuf::from(r"C:\ProgramData\triboferrin"),
+            config: Some(PathBuf::from(r"C:\ProgramData\triboferrin\config.toml")),
+            profile: Some("prod & test".to_string()),
+        };
+        let task = scheduled_task("triboferrin", &invocation);
+
+        assert!(task.contains(r"<Command>C:\Program Files\triboferrin\triboferrin.exe</Command>"));
+        assert!(task.contains(
+            r#"<Arguments>--config C:\ProgramData\triboferrin\config.toml --profile &quot;prod &amp; test&quot;</Arguments>"#
+        ));
+        assert!(task.contains(r"<WorkingDirectory>C:\ProgramData\triboferrin</WorkingDirectory>"));
+        assert!(task.contains(r"<URI>\triboferrin</URI>"));
+    }
+
+    #[rstest]
+    #[case("prod", "prod")]
+    #[case("", r#""""#)]
+    #[case(r"C:\My Files\", r#""C:\My Files\\""#)]
+    #[case(r#"say "hi""#, r#""say \"hi\"""#)]
+    #[case(r#"a\"b"#, r#""a\\\"b""#)]
+    fn test_windows_quote(#[case] arg: &str, #[case] expected: &str) {
+        assert_eq!(windows_quote(arg), expected);
+    }
+}
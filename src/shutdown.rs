@@ -0,0 +1,50 @@
+use serenity::gateway::ShardManager;
+use std::sync::Arc;
+
+use crate::panic;
+
+/// Wait until the process is asked to stop.
+/// Handles Ctrl+C everywhere, SIGTERM on Unix and console close/shutdown on Windows.
+#[cfg(unix)]
+async fn wait_for_signal() -> std::io::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+/// Wait until the process is asked to stop.
+/// Handles Ctrl+C everywhere, SIGTERM on Unix and console close/shutdown on Windows.
+#[cfg(windows)]
+async fn wait_for_signal() -> std::io::Result<()> {
+    use tokio::signal::windows::{ctrl_break, ctrl_close, ctrl_shutdown};
+
+    let mut brk = ctrl_break()?;
+    let mut close = ctrl_close()?;
+    let mut shutdown = ctrl_shutdown()?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = brk.recv() => Ok(()),
+        _ = close.recv() => Ok(()),
+        _ = shutdown.recv() => Ok(()),
+    }
+}
+
+/// Shut all shards down cleanly once a stop signal arrives, letting `Client::start` return.
+pub fn shutdown_on_signal(shard_manager: Arc<ShardManager>) {
+    tokio::spawn(async move {
+        if let Err(e) = wait_for_signal().await {
+            tracing::error!("Failed to listen for shutdown signals: {}", e);
+            return;
+        }
+
+        tracing::info!(
+            panics = panic::count(),
+            "Shutdown requested, disconnecting..."
+        );
+        shard_manager.shutdown_all().await;
+    });
+}
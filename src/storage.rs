@@ -0,0 +1,535 @@
+//! State that outlives the process, in a SQLite database.
+//!
+//! The schema lives in `migrations/` and is applied when the database is opened. Discord
+//! ids are stored as SQLite integers; snowflakes stay below 2^63, so they round-trip.
+
+use serenity::all::{GuildId, UserId};
+use sqlx::Row;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::player::queue::{QueuedTrack, Source};
+
+/// Plays remembered per guild; older ones are dropped.
+const HISTORY_LIMIT: i64 = 500;
+
+/// A saved playlist. Each track's requester is whoever added it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Playlist {
+    pub name: String,
+    pub owner: UserId,
+    pub tracks: Vec<QueuedTrack>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct PlaylistSummary {
+    pub name: String,
+    pub tracks: usize,
+}
+
+/// A track that started playing in a guild. Its requester is whoever queued it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Played {
+    pub id: i64,
+    pub track: QueuedTrack,
+    /// Unix seconds
+    pub played_at: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Storage {
+    pool: SqlitePool,
+}
+
+impl Storage {
+    /// Open the database at `path`, creating it and bringing its schema up to date.
+    pub async fn open(path: &Path) -> Result<Self, String> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .map_err(|e| format!("database_path {}: {e}", path.display()))?;
+        sqlx::migrate!()
+            .run(&pool)
+            .await
+            .map_err(|e| format!("Could not migrate {}: {e}", path.display()))?;
+        Ok(Self { pool })
+    }
+
+    /// Save `tracks` as a new playlist. Names are unique per guild, ignoring case.
+    pub async fn create_playlist(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        owner: UserId,
+        tracks: &[QueuedTrack],
+    ) -> Result<(), String> {
+        let mut transaction = self.pool.begin().await.map_err(failed)?;
+        let created = sqlx::query(
+            "INSERT INTO playlists (guild_id, name, owner_id) VALUES (?, ?, ?) \
+             ON CONFLICT DO NOTHING RETURNING id",
+        )
+        .bind(id(guild_id.get()))
+        .bind(name)
+        .bind(id(owner.get()))
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(failed)?;
+        let Some(row) = created else {
+            return Err(format!("A playlist named \"{name}\" already exists."));
+        };
+        let playlist_id: i64 = row.get(0);
+
+        for track in tracks {
+            insert_track(&mut transaction, playlist_id, track).await?;
+        }
+        transaction.commit().await.map_err(failed)
+    }
+
+    /// Append tracks to a playlist while it holds fewer than `limit`, leaving out those
+    /// that don't fit. Returns how many were added and how many it holds afterwards.
+    pub async fn add_tracks(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        tracks: &[QueuedTrack],
+        limit: usize,
+    ) -> Result<(usize, usize), String> {
+        // Takes the write lock up front, so concurrent adds count after each other
+        let mut transaction = self
+            .pool
+            .begin_with("BEGIN IMMEDIATE")
+            .await
+            .map_err(failed)?;
+        let playlist_id = playlist_id(&mut transaction, guild_id, name).await?;
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM playlist_tracks WHERE playlist_id = ?")
+                .bind(playlist_id)
+                .fetch_one(&mut *transaction)
+                .await
+                .map_err(failed)?;
+        let count = count as usize;
+        let room = limit.saturating_sub(count);
+        if room == 0 {
+            return Err(format!(
+                "\"{name}\" already holds {limit} tracks, the most a playlist can."
+            ));
+        }
+
+        let added = &tracks[..tracks.len().min(room)];
+        for track in added {
+            insert_track(&mut transaction, playlist_id, track).await?;
+        }
+        transaction.commit().await.map_err(failed)?;
+        Ok((added.len(), count + added.len()))
+    }
+
+    /// Remove the track at `index` (0-based) of a playlist.
+    pub async fn remove_track(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        index: usize,
+    ) -> Result<(), String> {
+        let mut transaction = self.pool.begin().await.map_err(failed)?;
+        let playlist_id = playlist_id(&mut transaction, guild_id, name).await?;
+        let removed = sqlx::query(
+            "DELETE FROM playlist_tracks WHERE id = (\
+             SELECT id FROM playlist_tracks WHERE playlist_id = ? ORDER BY id LIMIT 1 OFFSET ?)",
+        )
+        .bind(playlist_id)
+        .bind(index as i64)
+        .execute(&mut *transaction)
+        .await
+        .map_err(failed)?;
+        if removed.rows_affected() == 0 {
+            return Err(format!("\"{name}\" has no track {}.", index + 1));
+        }
+        transaction.commit().await.map_err(failed)
+    }
+
+    pub async fn playlist(&self, guild_id: GuildId, name: &str) -> Result<Playlist, String> {
+        let row =
+            sqlx::query("SELECT id, name, owner_id FROM playlists WHERE guild_id = ? AND name = ?")
+                .bind(id(guild_id.get()))
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(failed)?
+                .ok_or_else(|| not_found(name))?;
+        let playlist_id: i64 = row.get("id");
+
+        let tracks = sqlx::query(
+            "SELECT url, title, duration_ms, source, added_by FROM playlist_tracks \
+             WHERE playlist_id = ? ORDER BY id",
+        )
+        .bind(playlist_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(failed)?
+        .iter()
+        .map(|row| track(row, "added_by"))
+        .collect();
+
+        Ok(Playlist {
+            name: row.get("name"),
+            owner: UserId::new(row.get::<i64, _>("owner_id") as u64),
+            tracks,
+        })
+    }
+
+    /// A guild's playlists, by name.
+    pub async fn playlists(&self, guild_id: GuildId) -> Result<Vec<PlaylistSummary>, String> {
+        let rows = sqlx::query(
+            "SELECT name, (SELECT COUNT(*) FROM playlist_tracks WHERE playlist_id = playlists.id) \
+             FROM playlists WHERE guild_id = ? ORDER BY name",
+        )
+        .bind(id(guild_id.get()))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(failed)?;
+        Ok(rows
+            .iter()
+            .map(|row| PlaylistSummary {
+                name: row.get(0),
+                tracks: row.get::<i64, _>(1) as usize,
+            })
+            .collect())
+    }
+
+    /// Remember that `track` started playing at `played_at` (unix seconds).
+    pub async fn record_play(
+        &self,
+        guild_id: GuildId,
+        track: &QueuedTrack,
+        played_at: i64,
+    ) -> Result<(), String> {
+        let mut transaction = self.pool.begin().await.map_err(failed)?;
+        sqlx::query(
+            "INSERT INTO play_history \
+             (guild_id, url, title, duration_ms, source, requester, played_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(id(guild_id.get()))
+        .bind(&track.url)
+        .bind(&track.title)
+        .bind(track.duration.map(|duration| duration.as_millis() as i64))
+        .bind(source_name(track.source))
+        .bind(id(track.requester.get()))
+        .bind(played_at)
+        .execute(&mut *transaction)
+        .await
+        .map_err(failed)?;
+        sqlx::query(
+            "DELETE FROM play_history WHERE guild_id = ? AND id <= (\
+             SELECT id FROM play_history WHERE guild_id = ? ORDER BY id DESC LIMIT 1 OFFSET ?)",
+        )
+        .bind(id(guild_id.get()))
+        .bind(id(guild_id.get()))
+        .bind(HISTORY_LIMIT)
+        .execute(&mut *transaction)
+        .await
+        .map_err(failed)?;
+        transaction.commit().await.map_err(failed)
+    }
+
+    /// The last `limit` plays in a guild, newest first.
+    pub async fn history(&self, guild_id: GuildId, limit: usize) -> Result<Vec<Played>, String> {
+        let rows = sqlx::query(
+            "SELECT id, url, title, duration_ms, source, requester, played_at \
+             FROM play_history WHERE guild_id = ? ORDER BY id DESC LIMIT ?",
+        )
+        .bind(id(guild_id.get()))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(failed)?;
+        Ok(rows.iter().map(played).collect())
+    }
+
+    /// One play from a guild's history.
+    pub async fn played(&self, guild_id: GuildId, play_id: i64) -> Result<Played, String> {
+        sqlx::query(
+            "SELECT id, url, title, duration_ms, source, requester, played_at \
+             FROM play_history WHERE guild_id = ? AND id = ?",
+        )
+        .bind(id(guild_id.get()))
+        .bind(play_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(failed)?
+        .map(|row| played(&row))
+        .ok_or_else(|| "That track is no longer in the history.".to_string())
+    }
+}
+
+async fn playlist_id(
+    transaction: &mut sqlx::SqliteConnection,
+    guild_id: GuildId,
+    name: &str,
+) -> Result<i64, String> {
+    sqlx::query_scalar("SELECT id FROM playlists WHERE guild_id = ? AND name = ?")
+        .bind(id(guild_id.get()))
+        .bind(name)
+        .fetch_optional(transaction)
+        .await
+        .map_err(failed)?
+        .ok_or_else(|| not_found(name))
+}
+
+async fn insert_track(
+    transaction: &mut sqlx::SqliteConnection,
+    playlist_id: i64,
+    track: &QueuedTrack,
+) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO playlist_tracks (playlist_id, url, title, duration_ms, source, added_by) \
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(playlist_id)
+    .bind(&track.url)
+    .bind(&track.title)
+    .bind(track.duration.map(|duration| duration.as_millis() as i64))
+    .bind(source_name(track.source))
+    .bind(id(track.requester.get()))
+    .execute(transaction)
+    .await
+    .map_err(failed)?;
+    Ok(())
+}
+
+/// A track from a row, whose requester is in the `requester` column.
+fn track(row: &SqliteRow, requester: &str) -> QueuedTrack {
+    QueuedTrack {
+        url: row.get("url"),
+        title: row.get("title"),
+        duration: row
+            .get::<Option<i64>, _>("duration_ms")
+            .map(|ms| Duration::from_millis(ms as u64)),
+        requester: UserId::new(row.get::<i64, _>(requester) as u64),
+        source: match row.get::<&str, _>("source") {
+            "stream" => Source::Stream,
+            "file" => Source::File,
+            _ => Source::Ytdlp,
+        },
+    }
+}
+
+fn played(row: &SqliteRow) -> Played {
+    Played {
+        id: row.get("id"),
+        track: track(row, "requester"),
+        played_at: row.get("played_at"),
+    }
+}
+
+fn source_name(source: Source) -> &'static str {
+    match source {
+        Source::Ytdlp => "ytdlp",
+        Source::Stream => "stream",
+        Source::File => "file",
+    }
+}
+
+fn id(snowflake: u64) -> i64 {
+    snowflake as i64
+}
+
+fn not_found(name: &str) -> String {
+    format!("There is no playlist named \"{name}\".")
+}
+
+fn failed(e: sqlx::Error) -> String {
+    tracing::error!("Database query failed: {}", e);
+    "Could not reach the bot's database.".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn storage(name: &str) -> (std::path::PathBuf, Storage) {
+        let dir = std::env::temp_dir().join(format!("triboferrin_storage_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let storage = Storage::open(&dir.join("triboferrin.db")).await.unwrap();
+        (dir, storage)
+    }
+
+    fn track(title: &str, source: Source) -> QueuedTrack {
+        QueuedTrack {
+            url: format!("https://example.com/{title}"),
+            title: title.to_string(),
+            duration: Some(Duration::from_secs(180)),
+            requester: UserId::new(7),
+            source,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_playlist_round_trip() {
+        let (dir, storage) = storage("round_trip").await;
+        let guild = GuildId::new(1);
+        let tracks = vec![track("a", Source::Ytdlp), track("b", Source::Stream)];
+        storage
+            .create_playlist(guild, "Mix", UserId::new(3), &tracks)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            storage.playlist(guild, "mix").await.unwrap(),
+            Playlist {
+                name: "Mix".to_string(),
+                owner: UserId::new(3),
+                tracks,
+            }
+        );
+        assert!(storage.playlist(GuildId::new(2), "Mix").await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_name_rejected() {
+        let (dir, storage) = storage("duplicate").await;
+        let guild = GuildId::new(1);
+        let owner = UserId::new(3);
+        storage
+            .create_playlist(guild, "Mix", owner, &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.create_playlist(guild, "MIX", owner, &[]).await,
+            Err("A playlist named \"MIX\" already exists.".to_string())
+        );
+        storage
+            .create_playlist(GuildId::new(2), "Mix", owner, &[])
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_tracks() {
+        let (dir, storage) = storage("add_remove").await;
+        let guild = GuildId::new(1);
+        storage
+            .create_playlist(guild, "Mix", UserId::new(3), &[track("a", Source::Ytdlp)])
+            .await
+            .unwrap();
+
+        let added = [track("b", Source::File), track("c", Source::Ytdlp)];
+        assert_eq!(
+            storage.add_tracks(guild, "Mix", &added, 10).await,
+            Ok((2, 3))
+        );
+        storage.remove_track(guild, "Mix", 1).await.unwrap();
+        assert!(storage.remove_track(guild, "Mix", 5).await.is_err());
+
+        let titles: Vec<String> = storage
+            .playlist(guild, "Mix")
+            .await
+            .unwrap()
+            .tracks
+            .into_iter()
+            .map(|track| track.title)
+            .collect();
+        assert_eq!(titles, vec!["a", "c"]);
+        assert_eq!(
+            storage.playlists(guild).await.unwrap(),
+            vec![PlaylistSummary {
+                name: "Mix".to_string(),
+                tracks: 2,
+            }]
+        );
+        assert!(
+            storage
+                .add_tracks(guild, "Missing", &added, 10)
+                .await
+                .is_err()
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_tracks_limit() {
+        let (dir, storage) = storage("add_limit").await;
+        let guild = GuildId::new(1);
+        storage
+            .create_playlist(guild, "Mix", UserId::new(3), &[])
+            .await
+            .unwrap();
+
+        let added = [track("a", Source::Ytdlp), track("b", Source::Ytdlp)];
+        let adds = (0..8).map(|_| storage.add_tracks(guild, "Mix", &added, 5));
+        let added: usize = futures::future::join_all(adds)
+            .await
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(|(added, _)| added)
+            .sum();
+
+        assert_eq!(added, 5);
+        assert_eq!(
+            storage.playlist(guild, "Mix").await.unwrap().tracks.len(),
+            5
+        );
+        assert_eq!(
+            storage
+                .add_tracks(guild, "Mix", &[track("c", Source::Ytdlp)], 5)
+                .await,
+            Err("\"Mix\" already holds 5 tracks, the most a playlist can.".to_string())
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_history_newest_first_and_trimmed() {
+        let (dir, storage) = storage("history").await;
+        let guild = GuildId::new(1);
+        for n in 0..HISTORY_LIMIT + 2 {
+            storage
+                .record_play(guild, &track(&n.to_string(), Source::Ytdlp), 1_000 + n)
+                .await
+                .unwrap();
+        }
+        storage
+            .record_play(GuildId::new(2), &track("other", Source::File), 5)
+            .await
+            .unwrap();
+
+        let history = storage.history(guild, 2).await.unwrap();
+        let titles: Vec<&str> = history
+            .iter()
+            .map(|played| played.track.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["501", "500"]);
+        assert_eq!(history[0].played_at, 1_501);
+        assert_eq!(storage.history(guild, 1_000).await.unwrap().len(), 500);
+
+        assert_eq!(
+            storage.played(guild, history[1].id).await.unwrap(),
+            history[1]
+        );
+        let other = storage.history(GuildId::new(2), 5).await.unwrap();
+        assert!(storage.played(guild, other[0].id).await.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reopen_keeps_playlists() {
+        let (dir, storage) = storage("reopen").await;
+        storage
+            .create_playlist(GuildId::new(1), "Mix", UserId::new(3), &[])
+            .await
+            .unwrap();
+        drop(storage);
+
+        let reopened = Storage::open(&dir.join("triboferrin.db")).await.unwrap();
+        assert_eq!(reopened.playlists(GuildId::new(1)).await.unwrap().len(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
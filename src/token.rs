@@ -0,0 +1,68 @@
+use serenity::all::CurrentUser;
+use serenity::http::{Http, HttpError};
+
+/// Check that a token has the shape of a bot token: three non-empty, dot-separated parts.
+/// Catches client secrets and application ids pasted in place of the token before any request.
+pub fn check_format(token: &str) -> Result<(), String> {
+    let token = token.strip_prefix("Bot ").unwrap_or(token).trim();
+
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 || parts.iter().any(|part| part.is_empty()) {
+        return Err("The Discord token does not look like a bot token. \
+             Copy it from Bot > Token in the Discord Developer Portal; \
+             the client secret and application id are not tokens"
+            .to_string());
+    }
+
+    Ok(())
+}
+
+/// Verify the token against Discord before connecting the gateway.
+/// Returns the bot user the token belongs to.
+pub async fn validate(http: &Http) -> Result<CurrentUser, String> {
+    let user = http.get_current_user().await.map_err(|e| match e {
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(ref response))
+            if response.status_code.as_u16() == 401 =>
+        {
+            "Discord rejected the bot token (401 Unauthorized). \
+             It may have been reset; generate a new one under Bot > Token \
+             in the Discord Developer Portal"
+                .to_string()
+        }
+        serenity::Error::Http(HttpError::Request(ref e)) => {
+            format!("Could not reach the Discord API to validate the token: {e}")
+        }
+        e => format!("Token validation failed: {e}"),
+    })?;
+
+    if !user.bot {
+        return Err("The Discord token belongs to a user account, not a bot. \
+             Create a bot under Bot in the Discord Developer Portal and use its token"
+            .to_string());
+    }
+
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("MTIzNDU2Nzg5MDEyMzQ1Njc4.GaBcDe.abcdefghijklmnopqrstuvwxyz0123456789AB")]
+    #[case("Bot MTIzNDU2Nzg5MDEyMzQ1Njc4.GaBcDe.abcdefghijklmnopqrstuvwxyz0123456789AB")]
+    fn test_check_format_valid(#[case] token: &str) {
+        assert!(check_format(token).is_ok());
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("abcdefghijklmnopqrstuvwxyz012345")]
+    #[case("123456789012345678")]
+    #[case("MTIzNDU2Nzg5MDEyMzQ1Njc4..abcdef")]
+    #[case("a.b.c.d")]
+    fn test_check_format_invalid(#[case] token: &str) {
+        assert!(check_format(token).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Deserialize;
+use serenity::all::CreateMessage;
+use serenity::http::Http;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::access;
+use crate::config::UpdateConfig;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/mmannerm/triboferrin/releases/latest";
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Longest release notes excerpt sent to owners.
+const NOTES_LIMIT: usize = 1500;
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// Parse `major.minor.patch` from a version or tag such as `v1.2.3-rc.1`.
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+/// Whether `latest` is a newer release than `current`. Unparsable versions never are.
+fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// Shorten release notes to a DM-friendly length on a character boundary.
+fn excerpt(notes: &str) -> String {
+    let notes = notes.trim();
+    match notes.char_indices().nth(NOTES_LIMIT) {
+        Some((index, _)) => format!("{}…", &notes[..index]),
+        None => notes.to_string(),
+    }
+}
+
+async fn latest_release(client: &reqwest::Client) -> reqwest::Result<Release> {
+    client
+        .get(RELEASES_URL)
+        .header(
+            reqwest::header::USER_AGENT,
+            concat!("triboferrin/", env!("CARGO_PKG_VERSION")),
+        )
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+async fn notify_owners(http: &Http, release: &Release) {
+    let mut content = format!(
+        "Triboferrin {} is available (running {}): {}",
+        release.tag_name, CURRENT_VERSION, release.html_url
+    );
+    if let Some(notes) = release
+        .body
+        .as_deref()
+        .filter(|notes| !notes.trim().is_empty())
+    {
+        content.push_str(&format!("\n\n{}", excerpt(notes)));
+    }
+
+    let owners = match access::owners(http).await {
+        Ok(owners) => owners,
+        Err(e) => {
+            tracing::warn!("Could not look up application owners: {}", e);
+            return;
+        }
+    };
+
+    for owner in owners {
+        let message = CreateMessage::new().content(&content);
+        if let Err(e) = owner.direct_message(http, message).await {
+            tracing::warn!("Could not notify owner {} about the update: {}", owner, e);
+        }
+    }
+}
+
+/// Periodically check for a newer release, announcing each new version once.
+pub fn spawn(http: Arc<Http>, config: UpdateConfig) {
+    tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut interval = tokio::time::interval(Duration::from_secs(config.interval.max(60)));
+        let mut announced: Option<String> = None;
+
+        loop {
+            interval.tick().await;
+
+            let release = match latest_release(&client).await {
+                Ok(release) => release,
+                Err(e) => {
+                    tracing::debug!("Update check failed: {}", e);
+                    continue;
+                }
+            };
+
+            if !is_newer(&release.tag_name, CURRENT_VERSION)
+                || announced.as_deref() == Some(release.tag_name.as_str())
+            {
+                continue;
+            }
+
+            tracing::warn!(
+                "A newer version {} is available (running {}): {}",
+                release.tag_name,
+                CURRENT_VERSION,
+                release.html_url
+            );
+            if config.notify_owners {
+                notify_owners(&http, &release).await;
+            }
+            announced = Some(release.tag_name);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("1.2.3", Some((1, 2, 3)))]
+    #[case("v0.10.0", Some((0, 10, 0)))]
+    #[case("v1.0.0-rc.1", Some((1, 0, 0)))]
+    #[case("1.0.0+build.5", Some((1, 0, 0)))]
+    #[case("1.0", None)]
+    #[case("1.0.0.0", None)]
+    #[case("nightly", None)]
+    fn test_parse_version(#[case] version: &str, #[case] expected: Option<(u64, u64, u64)>) {
+        assert_eq!(parse_version(version), expected);
+    }
+
+    #[rstest]
+    #[case("v0.2.0", "0.1.0", true)]
+    #[case("v0.1.1", "0.1.0", true)]
+    #[case("v0.1.0", "0.1.0", false)]
+    #[case("v0.0.9", "0.1.0", false)]
+    #[case("latest", "0.1.0", false)]
+    fn test_is_newer(#[case] latest: &str, #[case] current: &str, #[case] expected: bool) {
+        assert_eq!(is_newer(latest, current), expected);
+    }
+
+    #[test]
+    fn test_excerpt_short_notes_unchanged() {
+        assert_eq!(excerpt("  Fixes  "), "Fixes");
+    }
+
+    #[test]
+    fn test_excerpt_truncates_long_notes() {
+        let notes = "ä".repeat(NOTES_LIMIT + 10);
+        let excerpt = excerpt(&notes);
+        assert_eq!(excerpt.chars().count(), NOTES_LIMIT + 1);
+        assert!(excerpt.ends_with('…'));
+    }
+}
@@ -0,0 +1,252 @@
+use serenity::all::{
+    CreateEmbed, CreateEmbedFooter, CreateInteractionResponseFollowup,
+    CreateInteractionResponseMessage, CreateMessage, EditInteractionResponse, GuildId,
+};
+use std::time::Duration;
+
+use crate::config::{EmbedStyle, ThemeConfig};
+
+/// Accent color used when none is configured.
+const DEFAULT_COLOR: u32 = 0x5865f2;
+
+/// A titled list of fields, shown as an embed or, in plain-text mode, as text lines.
+#[derive(Clone)]
+pub struct Card {
+    title: String,
+    description: Option<String>,
+    fields: Vec<(String, String, bool)>,
+}
+
+impl Card {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            description: None,
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn field(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+        inline: bool,
+    ) -> Self {
+        self.fields.push((name.into(), value.into(), inline));
+        self
+    }
+
+    /// Response message rendered for the guild it is shown in.
+    pub fn message(
+        self,
+        theme: &ThemeConfig,
+        guild_id: Option<GuildId>,
+    ) -> CreateInteractionResponseMessage {
+        let message = CreateInteractionResponseMessage::new();
+        match self.body(theme, guild_id) {
+            Body::Text(text) => message.content(text),
+            Body::Embed(embed) => message.embed(*embed),
+        }
+    }
+
+    /// Edit replacing a deferred response, rendered for the guild it is shown in.
+    pub fn edit(self, theme: &ThemeConfig, guild_id: Option<GuildId>) -> EditInteractionResponse {
+        let edit = EditInteractionResponse::new();
+        match self.body(theme, guild_id) {
+            Body::Text(text) => edit.content(text),
+            Body::Embed(embed) => edit.embed(*embed),
+        }
+    }
+
+    /// Followup to an interaction, rendered for the guild it is shown in.
+    pub fn followup(
+        self,
+        theme: &ThemeConfig,
+        guild_id: Option<GuildId>,
+    ) -> CreateInteractionResponseFollowup {
+        let followup = CreateInteractionResponseFollowup::new();
+        match self.body(theme, guild_id) {
+            Body::Text(text) => followup.content(text),
+            Body::Embed(embed) => followup.embed(*embed),
+        }
+    }
+
+    /// Channel message starting with `mention`, rendered for the guild it is shown in.
+    pub fn channel_message(
+        self,
+        theme: &ThemeConfig,
+        guild_id: Option<GuildId>,
+        mention: &str,
+    ) -> CreateMessage {
+        match self.body(theme, guild_id) {
+            Body::Text(text) => CreateMessage::new().content(format!("{mention} {text}")),
+            Body::Embed(embed) => CreateMessage::new().content(mention).embed(*embed),
+        }
+    }
+
+    fn body(self, theme: &ThemeConfig, guild_id: Option<GuildId>) -> Body {
+        if plain_text(theme, guild_id) {
+            Body::Text(self.text())
+        } else {
+            Body::Embed(Box::new(self.embed(theme, guild_id)))
+        }
+    }
+
+    fn embed(self, theme: &ThemeConfig, guild_id: Option<GuildId>) -> CreateEmbed {
+        let embed = embed(theme, guild_id).title(self.title).fields(self.fields);
+        match self.description {
+            Some(description) => embed.description(description),
+            None => embed,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = self.title.clone();
+        if let Some(ref description) = self.description {
+            text.push_str(&format!("\n{description}"));
+        }
+        for (name, value, _) in &self.fields {
+            text.push_str(&format!("\n{name}: {value}"));
+        }
+        text
+    }
+}
+
+/// A card as shown in a guild: lines of text in plain-text mode, an embed otherwise.
+enum Body {
+    Text(String),
+    Embed(Box<CreateEmbed>),
+}
+
+/// Blank embed styled with the theme of the guild it is shown in.
+fn embed(theme: &ThemeConfig, guild_id: Option<GuildId>) -> CreateEmbed {
+    let (color, footer) = style(theme, guild_id);
+    let embed = CreateEmbed::new().color(color);
+    match footer {
+        Some(footer) => embed.footer(CreateEmbedFooter::new(footer)),
+        None => embed,
+    }
+}
+
+fn guild_style(theme: &ThemeConfig, guild_id: Option<GuildId>) -> Option<&EmbedStyle> {
+    guild_id.and_then(|guild_id| theme.guilds.get(&guild_id.to_string()))
+}
+
+/// Color and footer for a guild, falling back to the global theme for unset values.
+fn style(theme: &ThemeConfig, guild_id: Option<GuildId>) -> (u32, Option<&str>) {
+    let guild = guild_style(theme, guild_id);
+    let color = guild
+        .and_then(|guild| guild.color)
+        .or(theme.style.color)
+        .unwrap_or(DEFAULT_COLOR);
+    let footer = guild
+        .and_then(|guild| guild.footer.as_deref())
+        .or(theme.style.footer.as_deref());
+    (color, footer)
+}
+
+/// Whether replies in a guild should be plain text rather than embeds.
+fn plain_text(theme: &ThemeConfig, guild_id: Option<GuildId>) -> bool {
+    guild_style(theme, guild_id)
+        .and_then(|guild| guild.plain_text)
+        .or(theme.style.plain_text)
+        .unwrap_or(false)
+}
+
+/// Format a track length as `m:ss`, or `h:mm:ss` for an hour or more.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::collections::BTreeMap;
+
+    fn theme() -> ThemeConfig {
+        ThemeConfig {
+            style: EmbedStyle {
+                color: Some(0x00ff00),
+                footer: Some("Global".to_string()),
+                plain_text: None,
+            },
+            guilds: BTreeMap::from([
+                (
+                    "111".to_string(),
+                    EmbedStyle {
+                        color: Some(0xff0000),
+                        footer: None,
+                        plain_text: Some(true),
+                    },
+                ),
+                (
+                    "222".to_string(),
+                    EmbedStyle {
+                        color: None,
+                        footer: Some("Guild".to_string()),
+                        plain_text: None,
+                    },
+                ),
+            ]),
+        }
+    }
+
+    #[rstest]
+    #[case(None, 0x00ff00, Some("Global"))]
+    #[case(Some(111), 0xff0000, Some("Global"))]
+    #[case(Some(222), 0x00ff00, Some("Guild"))]
+    #[case(Some(333), 0x00ff00, Some("Global"))]
+    fn test_style(#[case] guild_id: Option<u64>, #[case] color: u32, #[case] footer: Option<&str>) {
+        assert_eq!(style(&theme(), guild_id.map(GuildId::new)), (color, footer));
+    }
+
+    #[test]
+    fn test_style_default() {
+        assert_eq!(style(&ThemeConfig::default(), None), (DEFAULT_COLOR, None));
+    }
+
+    #[rstest]
+    #[case(None, false)]
+    #[case(Some(111), true)]
+    #[case(Some(222), false)]
+    fn test_plain_text(#[case] guild_id: Option<u64>, #[case] expected: bool) {
+        assert_eq!(plain_text(&theme(), guild_id.map(GuildId::new)), expected);
+    }
+
+    #[test]
+    fn test_card_text() {
+        let card = Card::new("Triboferrin")
+            .field("Version", "1.0.0", true)
+            .field("Uptime", "5s", true);
+        assert_eq!(card.text(), "Triboferrin\nVersion: 1.0.0\nUptime: 5s");
+    }
+
+    #[test]
+    fn test_card_text_with_description() {
+        let card = Card::new("Queue")
+            .description("1. First\n2. Second")
+            .field("Total", "2", true);
+        assert_eq!(card.text(), "Queue\n1. First\n2. Second\nTotal: 2");
+    }
+
+    #[rstest]
+    #[case(0, "0:00")]
+    #[case(65, "1:05")]
+    #[case(3600, "1:00:00")]
+    #[case(3725, "1:02:05")]
+    fn test_format_duration(#[case] secs: u64, #[case] expected: &str) {
+        assert_eq!(format_duration(Duration::from_secs(secs)), expected);
+    }
+}